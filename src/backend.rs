@@ -1,23 +1,33 @@
+use crate::{calc, dictionary, kana, learned, menu, state};
 use dns_lookup::lookup_host;
 use filemagic::{flags::Flags, FileMagicError, Magic};
 use fuse_rust::Fuse;
 // use regex::Regex;
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use fuzzy_matcher::{
+    skim::{SkimMatcherV2, SkimScoreConfig},
+    FuzzyMatcher,
+};
 use rayon::prelude::*;
 use serde_derive::{Deserialize, Serialize};
 use std::{
-    cmp::Reverse,
-    collections::{HashMap, HashSet},
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, HashMap, HashSet},
     env,
     error::Error,
     fs,
     hash::{Hash, Hasher},
-    io,
+    io::{self, BufRead, Write},
+    os::unix::process::CommandExt,
     path::Path,
-    process::{Child, Command},
-    sync::Arc,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+        Arc, Mutex,
+    },
     thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use unicode_normalization::UnicodeNormalization;
 use url::Url;
 
 // TODO: use config file
@@ -25,18 +35,290 @@ use url::Url;
 lazy_static! {
     pub static ref HOME_PATH: String = env::var("HOME").unwrap();
     pub static ref CONFIG_PATH: String = HOME_PATH.to_string() + "/.config/launcher/launcher.toml";
+    pub static ref LOG_PATH: String = HOME_PATH.to_string() + "/.cache/launcher/launcher.log";
+    pub static ref IGNORE_PATH: String = HOME_PATH.to_string() + "/.config/launcher/ignored";
+    // searches running and the last error of each source, see `source_status`
+    static ref SOURCES: Mutex<HashMap<&'static str, (usize, Option<String>)>> =
+        Mutex::new(HashMap::new());
+    // what the `state` command of each toggle last printed and when it was read, see
+    // `toggle_state`
+    static ref TOGGLE_STATES: Mutex<HashMap<String, (Instant, Option<bool>)>> =
+        Mutex::new(HashMap::new());
+    // apps found to have no settings item, see `Action::OpenSettings`
+    static ref NO_SETTINGS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    // hidden results, one path or URL per line of `IGNORE_PATH`
+    static ref IGNORED: Mutex<Vec<String>> = Mutex::new(
+        fs::read_to_string(IGNORE_PATH.as_str())
+            .unwrap_or_default()
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect()
+    );
+}
+
+// Remembered selections
+const MAX_RECENT: usize = 50;
+
+// See `refresh_interval`
+const LIST_DIR_REFRESH: Duration = Duration::from_secs(2);
+const TOGGLE_REFRESH: Duration = Duration::from_millis(200);
+
+// How long the state of a toggle is shown before it is read again
+const TOGGLE_STATE_AGE: Duration = Duration::from_secs(5);
+
+// `state` commands of toggles running, see `toggle_state`
+static READING_TOGGLES: AtomicU64 = AtomicU64::new(0);
+
+// Append a line to the log file. The terminal is owned by the UI so nothing is printed
+pub fn log(msg: &str) {
+    let path = Path::new(LOG_PATH.as_str());
+    if let Some(p) = path.parent() {
+        let _ = fs::create_dir_all(p);
+    }
+    if let Ok(mut f) = fs::OpenOptions::new().create(true).append(true).open(path) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let _ = writeln!(f, "[{}] {}", time, msg);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SourceStatus {
+    Ready,
+    Searching,
+    Failed(String),
+}
+
+// Marks a source as searching until dropped
+pub struct Searching(&'static str);
+
+impl Searching {
+    pub fn start(source: &'static str) -> Searching {
+        SOURCES.lock().unwrap().entry(source).or_default().0 += 1;
+        Searching(source)
+    }
+}
+
+impl Drop for Searching {
+    fn drop(&mut self) {
+        if let Some(state) = SOURCES.lock().unwrap().get_mut(self.0) {
+            state.0 -= 1;
+        }
+    }
+}
+
+// What went wrong with the last search of `source`, `None` when it worked
+pub fn set_source_error(source: &'static str, error: Option<String>) {
+    SOURCES.lock().unwrap().entry(source).or_default().1 = error;
+}
+
+// The enabled sources that can be slow or fail
+pub fn sources(config: &Config) -> Vec<&'static str> {
+    ["index", "url", "dictionary", "menu"]
+        .into_iter()
+        .filter(|s| match *s {
+            "dictionary" => !config.define_keyword.is_empty(),
+            "menu" => !config.menu_keyword.is_empty(),
+            _ => true,
+        })
+        .collect()
+}
+
+pub fn source_status(source: &str) -> SourceStatus {
+    match SOURCES.lock().unwrap().get(source) {
+        Some((n, _)) if *n > 0 => SourceStatus::Searching,
+        Some((_, Some(e))) => SourceStatus::Failed(e.clone()),
+        _ => SourceStatus::Ready,
+    }
+}
+
+// `:<name> [args]` runs `run` with the arguments appended
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct UserCommand {
+    pub run: String,
+    pub env: BTreeMap<String, String>, // added to the launcher's environment
+}
+
+// A setting switched with `<name> on|off` or `toggle <name>`. Shell commands, `state` prints
+// the current value, `1`, `true`, `on` or `yes` when on
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct Toggle {
+    pub state: String, // left empty if it can't be read, both directions are offered then
+    pub on: String,
+    pub off: String,
 }
 
 #[derive(Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
-    app_locations: Vec<String>,
-    editor: String,       // path to binary
-    results_len: usize,   // show how many results
-    fuzzy_engine: String, // 'fuse' or 'skim'. Use skim if fuse is too slow
+    pub app_locations: Vec<String>,
+    pub editor: String,              // path to binary
+    pub results_len: usize,          // show how many results
+    pub fuzzy_engine: String,        // 'fuse' or 'skim'. Use skim if fuse is too slow
+    pub score_normalization: String, // 'none', 'min-max' or 'z-score', see `merge_scored`
+    pub dir_trailing_slash: bool,    // append '/' when completing a directory
+    pub commit_trailing_space: bool, // append ' ' when completing a result that isn't a path
+    pub refresh_selection: String,   // after results refresh: 'id', 'index' or 'top'
+    pub group_results: bool,         // list results under a heading per kind, e.g. `Files (12)`
+    pub group_cap: usize,            // results shown per group until expanded, 0 for all
+    pub tabs: bool,                  // show `modes` as tabs above the results
+    pub env_file: Option<String>,    // sourced at startup for vars missing from a hotkey daemon
+    pub launchctl_vars: Vec<String>, // read with `launchctl getenv` if not set
+    // how to order equally scored results of a kind ('app', 'bin', 'file'):
+    // 'mtime' (newest first), 'frecency' (most picked lately first, see `learned`), 'name'
+    // or 'none'
+    pub tie_break: HashMap<String, String>,
+    pub keys: HashMap<String, String>,  // action name -> keys
+    pub capture_keyword: String,        // `<keyword> <text>` creates a reminder or note
+    pub capture_app: String,            // 'Reminders' or 'Notes'
+    pub capture_list: String,           // reminders list or notes folder, default one if empty
+    pub menu_keyword: String,           // `<keyword> <item>` clicks a menu item of the front app
+    pub define_keyword: String,         // `<keyword> <word>` looks the word up in the dictionary
+    pub kill_signal: String,            // sent by the `kill` key to a `:port` process, e.g. 'TERM'
+    pub email_keyword: String,          // `<keyword> <to> about <subject>` composes an email
+    pub email_pattern: String,          // how that is read, with `{to}` and `{subject}`
+    pub previewer: String,              // 'qlmanage' (Quick Look) or 'preview' (Preview.app)
+    pub more_indicator: bool,           // show `+N more` below a list taller than the screen
+    pub max_rendered: usize,            // results drawn around the selection, the others only count
+    pub highlight_matches: bool,        // what the query matched, see `Theme::match_exact`
+    pub preview_pane: bool,             // show the selected file next to the results
+    pub highlight_symbol_right: String, // marker at the right end of the selected result
+    pub action_icons: bool,             // what each result can do at the right of its row
+    pub expand_selected_subtitle: bool, // wrap the selected result onto more lines if too long
+    pub appearance: String,             // 'dark' or 'light' terminal
+    pub markdown_preview: bool,         // render Markdown files in the preview pane
+    pub empty_query: String,            // what an empty query lists: 'none', 'all' or 'recent'
+    // selected when recent results are listed: 'first', 'second' or 'keep'
+    pub recent_select: String,
+    pub calc_digits: usize,                // significant digits
+    pub calc_copy_digits: Option<usize>,   // of the copied value, as precise as possible if unset
+    pub calc_history_file: Option<String>, // keeps `ans` and `=` across sessions
+    pub commands: HashMap<String, UserCommand>,
+    // added to `builtin_toggles`, replacing those of the same name
+    pub toggles: HashMap<String, Toggle>,
+    pub delete_forward: bool, // Delete removes the character after the cursor, else the one before
+    pub modes: Vec<String>,   // switched to with Ctrl+1..9, see `Scope::from_mode`
+    // start with the clipboard as the query, also `--query-from-clipboard`
+    pub query_from_clipboard: bool,
+    pub clipboard_max_len: usize, // bytes, larger clipboards are left alone
+    pub on_accept: String,        // after launching: 'hide-terminal', 'close-window' or 'none'
+    pub on_cancel: String,        // the same after quitting without launching
+    pub max_fps: u32,             // redraws per second at most, 0 for no limit
+    pub plain: bool,              // no styling and ASCII only, also `--plain`
+    pub debug_keys: bool,         // show keys that are not bound to anything
+    pub top_hit: bool,            // show a clearly best result above the others
+    pub top_hit_min_score: i64,   // per query character, for the top hit
+    pub top_hit_margin: i64,      // the top hit scores this much higher than the next result
+    pub cursor_wrap: bool,        // Left and Right wrap around the query
+    // result kind -> what Enter does, see `enter_actions` and `fill_template`
+    pub enter: HashMap<String, String>,
+    pub columns: bool,             // short results in several columns
+    pub detail_column: bool,       // the directory of files right-aligned, see `name_and_detail`
+    pub tab_enter: String,         // Enter after Tab: 'launch' the result or 'complete' the query
+    pub completion_typing: String, // typing after Tab: 'commit' the completion or 'cancel' it
+    // what the `quit` key does: 'cancel', 'copy' the selection or 'double' to need two presses
+    pub quit_key: String,
+    // start with Enter adding the result to the query, the `build_query` key toggles it
+    pub build_query: bool,
+    pub learn_selections: bool,        // a pick comes first the next time
+    pub learn_half_life_days: f64,     // a learned pick counts half after this many days
+    pub state_dir: String,             // where recent, last and learned picks are kept, see `state`
+    pub state_format: String,          // 'json' or 'toml'
+    pub history_dedup: bool,           // a calculation repeating the last one is not saved again
+    pub history_exclude: Vec<String>,  // what contains one of these is not saved to history files
+    pub history_exclude_secrets: bool, // nor what has a word that looks like a password or token
+    // app name -> Space it is opened on by `open_in_space`, "*" for any
+    pub spaces: HashMap<String, u32>,
+    pub insert_method: String, // 'paste' through the clipboard or 'type' keystroke by keystroke
+    pub insert_delay_ms: u64,  // between keystrokes when typing, 0 types the text at once
+    pub kana_romaji: bool,     // romaji and kana names find each other, e.g. `surakku` `スラック`
+    // clear the screen before leaving it, for terminals without an alternate screen
+    pub exit_clear: bool,
+    pub exit_print: bool,           // print what was launched after leaving
+    pub broken_pipe_code: i32,      // exit code when what reads that went away, e.g. `head`
+    pub show_provider_status: bool, // a dot per source below the results: searching, failed, ready
+    pub show_footer: bool,          // the version and the config file read on the last row
+    #[serde(skip)]
+    pub loaded_from: Option<String>, // the file `from_file` read, `None` if it used the defaults
+    pub matcher: Matcher,
+    pub theme: Theme,
 }
 
-impl Config {
-    pub fn default() -> Config {
+// Colors of the results list, see `frontend::parse_color` for the names
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct Theme {
+    pub selection_fg: String,
+    pub selection_bg: String,
+    pub marker: String,      // `>>` of the selected result, its color if empty
+    pub match_exact: String, // characters of a result the query is a part of, see `parse_style`
+    pub match_fuzzy: String, // those the query matched apart
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            selection_fg: "black".to_string(),
+            selection_bg: "white".to_string(),
+            marker: String::new(),
+            match_exact: "underline".to_string(),
+            match_fuzzy: "bold".to_string(),
+        }
+    }
+}
+
+// Weights of the skim matcher, added to a result's score per matched query character. See
+// the [matcher] table in READEME.md for their effect
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct Matcher {
+    pub word_start: i32,            // it starts the name or follows a space or `/`
+    pub after_separator: i32,       // it follows `_`, `-`, `.` or other punctuation
+    pub camel_case: i32,            // it is upper case after a lower case letter or a digit
+    pub consecutive: i32,           // it follows the character matched before
+    pub first_char_multiplier: i32, // for the bonus of the first character matched
+    pub gap_start: i32,             // for skipping characters between two matched ones
+    pub gap_extension: i32,         // per character skipped
+}
+
+impl Default for Matcher {
+    // skim's own
+    fn default() -> Self {
+        Matcher {
+            word_start: 8,
+            after_separator: 7,
+            camel_case: 6,
+            consecutive: 4,
+            first_char_multiplier: 2,
+            gap_start: -3,
+            gap_extension: -1,
+        }
+    }
+}
+
+impl Matcher {
+    pub fn skim(&self) -> SkimMatcherV2 {
+        let defaults = SkimScoreConfig::default();
+        SkimMatcherV2::default().score_config(SkimScoreConfig {
+            bonus_head: self.word_start,
+            bonus_break: self.after_separator,
+            bonus_camel: self.camel_case,
+            bonus_consecutive: self.consecutive,
+            bonus_first_char_multiplier: self.first_char_multiplier,
+            gap_start: self.gap_start,
+            gap_extension: self.gap_extension,
+            ..defaults
+        })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
         Config {
             app_locations: vec![
                 "/Applications".to_string(),
@@ -47,14 +329,185 @@ impl Config {
             editor: "hx".to_string(),
             results_len: 20,
             fuzzy_engine: "skim".to_string(),
+            score_normalization: "none".to_string(),
+            dir_trailing_slash: false,
+            commit_trailing_space: false,
+            refresh_selection: "id".to_string(),
+            group_results: false,
+            group_cap: 5,
+            tabs: false,
+            env_file: None,
+            launchctl_vars: vec![],
+            tie_break: HashMap::from([("file".to_string(), "mtime".to_string())]),
+            keys: HashMap::new(),
+            capture_keyword: "remind".to_string(),
+            capture_app: "Reminders".to_string(),
+            capture_list: String::new(),
+            menu_keyword: "menu".to_string(),
+            define_keyword: "define".to_string(),
+            kill_signal: "TERM".to_string(),
+            email_keyword: "email".to_string(),
+            email_pattern: "{to} about {subject}".to_string(),
+            previewer: "qlmanage".to_string(),
+            more_indicator: true,
+            max_rendered: 1000,
+            highlight_matches: true,
+            preview_pane: false,
+            highlight_symbol_right: String::new(),
+            action_icons: false,
+            expand_selected_subtitle: false,
+            appearance: "dark".to_string(),
+            markdown_preview: true,
+            empty_query: "none".to_string(),
+            recent_select: "first".to_string(),
+            calc_digits: 6,
+            calc_copy_digits: None,
+            calc_history_file: None,
+            commands: HashMap::new(),
+            toggles: HashMap::new(),
+            delete_forward: true,
+            modes: ["all", "apps", "files", "bins"]
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            query_from_clipboard: false,
+            clipboard_max_len: 4096,
+            on_accept: "none".to_string(),
+            on_cancel: "none".to_string(),
+            max_fps: 60,
+            plain: false,
+            debug_keys: false,
+            top_hit: true,
+            top_hit_min_score: 16,
+            top_hit_margin: 10,
+            cursor_wrap: false,
+            enter: HashMap::new(),
+            columns: false,
+            detail_column: false,
+            tab_enter: "launch".to_string(),
+            completion_typing: "commit".to_string(),
+            quit_key: "cancel".to_string(),
+            build_query: false,
+            show_provider_status: false,
+            matcher: Matcher::default(),
+            learn_selections: true,
+            learn_half_life_days: 30.0,
+            state_dir: "~/.cache/launcher".to_string(),
+            state_format: "json".to_string(),
+            history_dedup: true,
+            history_exclude: vec![],
+            history_exclude_secrets: true,
+            spaces: HashMap::new(),
+            insert_method: "paste".to_string(),
+            insert_delay_ms: 0,
+            kana_romaji: false,
+            exit_clear: false,
+            exit_print: false,
+            broken_pipe_code: 141,
+            show_footer: false,
+            loaded_from: None,
+            theme: Theme::default(),
         }
     }
+}
 
+impl Config {
     pub fn from_file(path: &str) -> Config {
-        if let Ok(s) = fs::read_to_string(path) {
-            toml::from_str(&s).unwrap_or_else(|_| Config::default())
-        } else {
-            Config::default()
+        match fs::read_to_string(path).map(|s| toml::from_str::<Config>(&s)) {
+            Ok(Ok(config)) => Config {
+                loaded_from: Some(path.to_string()),
+                ..config
+            },
+            _ => Config::default(),
+        }
+    }
+
+    // Problems with the `[enter]` table
+    pub fn enter_errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+        for (kind, action) in &self.enter {
+            match ResultKind::from_name(kind) {
+                None => errors.push(format!("unknown result kind `{}`", kind)),
+                Some(k) if !enter_actions(k).contains(&action.as_str()) && !is_template(action) => {
+                    errors.push(format!(
+                        "`{}` is not an Enter action for `{}`, try {} or a command with `{{}}`",
+                        action,
+                        kind,
+                        enter_actions(k).join(", ")
+                    ))
+                }
+                _ => {}
+            }
+        }
+        errors.sort();
+        errors
+    }
+
+    // Add variables from `env_file` and `launchctl` to our environment.
+    // Must be called before spawning any thread
+    pub fn load_env(&self) {
+        if let Some(file) = &self.env_file {
+            let file = expand_path(file);
+            match Command::new("bash")
+                .arg("-c")
+                .arg("set -a; source \"$1\" >/dev/null; env -0")
+                .arg("bash")
+                .arg(&file)
+                .output()
+            {
+                Ok(output) if output.status.success() => {
+                    for var in output.stdout.split(|b| *b == 0) {
+                        let var = String::from_utf8_lossy(var);
+                        if let Some((key, value)) = var.split_once('=') {
+                            if env::var_os(key).is_none() {
+                                env::set_var(key, value);
+                            }
+                        }
+                    }
+                }
+                _ => log(&format!("failed to source env file `{}`", file)),
+            }
+        }
+        for key in &self.launchctl_vars {
+            if env::var_os(key).is_some() {
+                continue;
+            }
+            match Command::new("launchctl").arg("getenv").arg(key).output() {
+                Ok(output) if output.status.success() && !output.stdout.is_empty() => {
+                    let value = String::from_utf8_lossy(&output.stdout);
+                    env::set_var(key, value.trim_end_matches('\n'));
+                }
+                _ => log(&format!("`launchctl getenv {}` returned nothing", key)),
+            }
+        }
+    }
+
+    // `frecency` is `learned::frecency`, for the kinds ordered by it
+    fn tie_break(&self, a: &FileEntry, b: &FileEntry, frecency: &HashMap<String, f64>) -> Ordering {
+        if a.file_type != b.file_type {
+            return Ordering::Equal;
+        }
+        let kind = match a.file_type {
+            FileEntryType::App => "app",
+            FileEntryType::Bin => "bin",
+            FileEntryType::File => "file",
+        };
+        match self.tie_break.get(kind).map(String::as_str) {
+            Some("mtime") => b.modified.cmp(&a.modified),
+            Some("frecency") => {
+                let picked = |e: &FileEntry| {
+                    let path = e.full_path.clone();
+                    let result = match e.file_type {
+                        FileEntryType::App => LauncherResult::App(path),
+                        FileEntryType::Bin => LauncherResult::Bin(path),
+                        FileEntryType::File => LauncherResult::File(path),
+                    };
+                    frecency.get(&result.get_string()).copied().unwrap_or(0.0)
+                };
+                picked(b).total_cmp(&picked(a))
+            }
+            Some("name") => a.name.cmp(&b.name),
+            _ => Ordering::Equal,
         }
     }
 
@@ -68,7 +521,8 @@ impl Config {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(tag = "kind", content = "value")] // toml has no other way to store enums in arrays
 pub enum LauncherResult {
     Command(String, String), // command description?
     Url(String),             // opens browser
@@ -76,6 +530,353 @@ pub enum LauncherResult {
     Bin(String),
     File(String),
     // WebSearch(String), // Retrieve google results
+    Privileged(String, String),   // description, shell command run as root
+    AppWith(String, String),      // app path, arguments typed after the app name
+    Script(String, String),       // description, AppleScript
+    Calc(String, String, String), // expression, displayed value, copied value (empty on errors)
+    Shell(String, String, Vec<(String, String)>), // description, shell command, environment
+    Definition(String, String),   // word, definition
+    Process(String, Option<u32>, String), // description or error, pid (`None` on errors), signal
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResultKind {
+    Command,
+    Url,
+    App,
+    Bin,
+    File,
+}
+
+impl ResultKind {
+    // As in the `[enter]` table of the config
+    fn from_name(name: &str) -> Option<ResultKind> {
+        match name {
+            "command" => Some(ResultKind::Command),
+            "url" => Some(ResultKind::Url),
+            "app" => Some(ResultKind::App),
+            "bin" => Some(ResultKind::Bin),
+            "file" => Some(ResultKind::File),
+            _ => None,
+        }
+    }
+
+    // Heading of the group, see `Config::group_results`
+    pub fn title(&self) -> &'static str {
+        match self {
+            ResultKind::Command => "Commands",
+            ResultKind::Url => "URLs",
+            ResultKind::App => "Applications",
+            ResultKind::Bin => "Binaries",
+            ResultKind::File => "Files",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ResultKind::Command => "command",
+            ResultKind::Url => "url",
+            ResultKind::App => "app",
+            ResultKind::Bin => "bin",
+            ResultKind::File => "file",
+        }
+    }
+}
+
+// An `[enter]` value with a placeholder is a shell command rather than one of `enter_actions`
+fn is_template(s: &str) -> bool {
+    ["{}", "{name}", "{dir}"].iter().any(|p| s.contains(p))
+}
+
+// `template` for `result`, `{}` replaced with its path, URL or value, `{name}` with the file
+// name and `{dir}` with the directory it is in. Each is shell quoted
+pub fn fill_template(template: &str, result: &LauncherResult) -> String {
+    let text = result.text();
+    let path = Path::new(text.trim_end_matches('/'));
+    let name = path
+        .file_name()
+        .map_or(text.clone(), |n| n.to_string_lossy().to_string());
+    let dir = match result.path() {
+        Some(_) => path
+            .parent()
+            .map_or(String::new(), |p| p.to_string_lossy().to_string()),
+        None => String::new(),
+    };
+    // in one pass, a file name may contain `{}` itself
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(i) = rest.find('{') {
+        filled.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let placeholder = [("{}", &text), ("{name}", &name), ("{dir}", &dir)]
+            .into_iter()
+            .find(|(p, _)| rest.starts_with(p));
+        match placeholder {
+            Some((p, value)) => {
+                filled.push_str(&shell_quote(value));
+                rest = &rest[p.len()..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    filled + rest
+}
+
+// What `Config::enter` can make Enter do for a kind of results. `open` is the built-in default
+fn enter_actions(kind: ResultKind) -> &'static [&'static str] {
+    match kind {
+        ResultKind::Command | ResultKind::Url => &["open", "copy", "insert"],
+        ResultKind::App => &[
+            "open",
+            "reveal",
+            "copy",
+            "insert",
+            "open-with",
+            "terminal-here",
+        ],
+        ResultKind::Bin | ResultKind::File => {
+            &["open", "reveal", "copy", "insert", "terminal-here"]
+        }
+    }
+}
+
+// Something to do with a result other than selecting it
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", content = "value")]
+pub enum Action {
+    CopyText(String),
+    Trash(String),            // path
+    Ignore(String),           // `identity` of the result
+    Reveal(String),           // path, selected in Finder
+    TerminalHere(String),     // directory
+    OpenInSpace(String, u32), // app path, number of the Space, see `Config::spaces`
+    Kill(u32, String),        // pid, signal
+    Insert(String),           // pasted into the app in front before the launcher
+    Rename(String, String),   // path, new file name
+    Move(String, String),     // path, directory it is moved into
+    Run(String),              // shell command, from an `[enter]` template
+    OpenSettings(String),     // app path
+}
+
+impl Action {
+    // What is asked for before the action runs, its prompt and the text it starts with. The
+    // answer is given to `with_argument`
+    pub fn argument(&self) -> Option<(&'static str, String)> {
+        let path = match self {
+            Action::Rename(path, _) | Action::Move(path, _) => {
+                Path::new(path.trim_end_matches('/'))
+            }
+            _ => return None,
+        };
+        Some(match self {
+            Action::Rename(..) => (
+                "Rename to>",
+                path.file_name()
+                    .map_or(String::new(), |n| n.to_string_lossy().to_string()),
+            ),
+            _ => (
+                "Move to>",
+                path.parent()
+                    .map_or(String::new(), |p| format!("{}/", p.to_string_lossy())),
+            ),
+        })
+    }
+
+    pub fn with_argument(self, argument: String) -> Action {
+        match self {
+            Action::Rename(path, _) => Action::Rename(path, argument),
+            Action::Move(path, _) => Action::Move(path, argument),
+            action => action,
+        }
+    }
+
+    // Returns a message to flash to the user
+    pub fn run(&self, config: &Config) -> Result<String, Box<dyn Error>> {
+        match self {
+            Action::CopyText(s) => {
+                if s.is_empty() {
+                    return Err("Nothing to copy".into());
+                }
+                copy_to_clipboard(s)?;
+                Ok(format!("Copied {}", s))
+            }
+            Action::Trash(path) => {
+                // through Finder, so the item can be put back and volume rules are respected
+                let script = format!(
+                    "tell application \"Finder\" to delete POSIX file {}",
+                    applescript_string(path)
+                );
+                let output = Command::new("osascript").arg("-e").arg(script).output()?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(format!("Failed to trash `{}`: {}", path, stderr.trim()).into());
+                }
+                Ok(format!("Moved {} to Trash", path))
+            }
+            Action::Ignore(identity) => {
+                ignore(identity)?;
+                Ok(format!("Hid {}", identity))
+            }
+            Action::Reveal(path) => {
+                Command::new("open").arg("-R").arg(path).spawn()?.wait()?;
+                Ok(format!("Revealed {}", path))
+            }
+            Action::TerminalHere(dir) => {
+                // iTerm and Terminal open a window in the directory they are given
+                let app = match env::var("TERM_PROGRAM").as_deref() {
+                    Ok("iTerm.app") => "iTerm",
+                    _ => "Terminal",
+                };
+                Command::new("open")
+                    .args(["-a", app])
+                    .arg(dir)
+                    .spawn()?
+                    .wait()?;
+                Ok(format!("Opened {} in {}", app, dir))
+            }
+            Action::OpenInSpace(path, space) => {
+                // macOS has no public API for Spaces. yabai switches first so new windows
+                // open there, which needs its scripting addition
+                let switched = Command::new("yabai")
+                    .args(["-m", "space", "--focus", &space.to_string()])
+                    .output();
+                Command::new("open").arg(path).spawn()?.wait()?;
+                Ok(match switched {
+                    Ok(o) if o.status.success() => format!("Opened {} on Space {}", path, space),
+                    Ok(o) => {
+                        let stderr = String::from_utf8_lossy(&o.stderr);
+                        log(&format!(
+                            "yabai failed to focus Space {}: {}",
+                            space,
+                            stderr.trim()
+                        ));
+                        format!("Opened {} on this Space, yabai could not switch", path)
+                    }
+                    Err(_) => format!("Opened {} on this Space, Spaces need yabai", path),
+                })
+            }
+            Action::OpenSettings(path) => {
+                let name = Path::new(path).file_stem().unwrap_or_default();
+                let name = name.to_string_lossy();
+                match applescript_output(&settings_script(path))?.trim() {
+                    "none" => {
+                        // not offered again
+                        NO_SETTINGS.lock().unwrap().insert(path.to_string());
+                        Err(format!("{} has no settings", name).into())
+                    }
+                    _ => Ok(format!("Opened {} settings", name)),
+                }
+            }
+            Action::Kill(pid, signal) => {
+                kill(*pid, signal)?;
+                Ok(format!("Sent SIG{} to pid {}", signal, pid))
+            }
+            Action::Insert(text) => {
+                if text.is_empty() {
+                    return Err("Nothing to insert".into());
+                }
+                match config.insert_method.as_str() {
+                    "type" => type_text(text, config.insert_delay_ms)?,
+                    "paste" => insert(text)?,
+                    other => {
+                        log(&format!(
+                            "unknown insert_method `{}`, pasting instead",
+                            other
+                        ));
+                        insert(text)?
+                    }
+                }
+                Ok(format!("Inserted {}", text))
+            }
+            Action::Rename(path, name) => {
+                let name = name.trim();
+                if name.is_empty() || name.contains('/') {
+                    return Err(format!("`{}` is not a file name", name).into());
+                }
+                let to = Path::new(path.trim_end_matches('/')).with_file_name(name);
+                if to.exists() {
+                    return Err(format!("`{}` already exists", to.display()).into());
+                }
+                fs::rename(path, &to)?;
+                Ok(format!("Renamed {} to {}", path, name))
+            }
+            Action::Move(path, dir) => {
+                let dir = expand_path(dir.trim());
+                if !Path::new(&dir).is_dir() {
+                    return Err(format!("`{}` is not a directory", dir).into());
+                }
+                let from = Path::new(path.trim_end_matches('/'));
+                let to = Path::new(&dir).join(from.file_name().unwrap_or_default());
+                if to.exists() {
+                    return Err(format!("`{}` already exists", to.display()).into());
+                }
+                // unlike `fs::rename`, also across volumes
+                let output = Command::new("mv").arg(from).arg(&to).output()?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(format!("Failed to move `{}`: {}", path, stderr.trim()).into());
+                }
+                Ok(format!("Moved {} to {}", path, dir))
+            }
+            Action::Run(cmd) => {
+                // no terminal, the UI is still up. In a group of its own so it outlives the
+                // launcher and its Ctrl+C
+                let mut child = Command::new("bash")
+                    .arg("-l")
+                    .arg("-c")
+                    .arg(cmd)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .process_group(0)
+                    .spawn()?;
+                let ran = cmd.clone();
+                // reaped in the background, an error exit is only logged
+                thread::spawn(move || match child.wait() {
+                    Ok(status) if !status.success() => log(&format!("`{}` {}", ran, status)),
+                    Ok(_) => {}
+                    Err(e) => log(&format!("`{}`: {}", ran, e)),
+                });
+                Ok(format!("Started {}", cmd))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResultAction {
+    pub name: &'static str, // also the name of its key binding
+    pub label: String,
+    pub action: Action,
+    pub confirmation: Option<String>, // asked before running the action
+}
+
+impl ResultAction {
+    // Shown at the right of rows with `action_icons`. `None` for the actions most results have
+    pub fn icon(&self) -> Option<&'static str> {
+        match self.name {
+            "copy_path" | "copy_filename" | "copy_escaped_path" | "copy_line" => Some("⧉"),
+            "open_in_space" => Some("↗"),
+            "rename" | "move" => Some("✎"),
+            "trash" => Some("⌫"),
+            "kill" => Some("✕"),
+            _ => None,
+        }
+    }
+}
+
+// The icons of `actions`, each once, in the order of the actions
+pub fn action_icons(actions: &[ResultAction]) -> String {
+    let mut icons = String::new();
+    for icon in actions.iter().filter_map(|a| a.icon()) {
+        if !icons.contains(icon) {
+            icons.push_str(icon);
+        }
+    }
+    icons
 }
 
 impl LauncherResult {
@@ -94,6 +895,22 @@ impl LauncherResult {
                 spawn_process(&format!("{}", path))?.wait()?;
                 return Ok(true);
             }
+            Self::Privileged(..) | Self::Script(..) | Self::Process(..) => {
+                self.run_in_place().unwrap()?;
+            }
+            Self::Calc(_, _, value) | Self::Definition(_, value) => copy_to_clipboard(value)?,
+            Self::Shell(_, cmd, env) => {
+                spawn_process_with_env(cmd, env)?.wait()?;
+                return Ok(true);
+            }
+            Self::AppWith(path, args) => {
+                Command::new("open")
+                    .arg("-a")
+                    .arg(path)
+                    .args(open_arguments(args))
+                    .spawn()?
+                    .wait()?;
+            }
             Self::File(path) => {
                 let magic = magic_cookie
                     .file(path)
@@ -112,8 +929,306 @@ impl LauncherResult {
         return Ok(false);
     }
 
-    fn prerun_command(self, cache: &Cache) -> io::Result<Vec<LauncherResult>> {
+    // Results that don't need the terminal are run before the UI exits, so failures can be
+    // shown in it. `None` for the others
+    pub fn run_in_place(&self) -> Option<Result<(), Box<dyn Error>>> {
+        match self {
+            Self::Privileged(_, cmd) => Some(run_privileged(cmd)),
+            Self::Script(_, script) => Some(run_applescript(script)),
+            Self::Process(_, Some(pid), signal) => Some(kill(*pid, signal)),
+            Self::Process(error, None, _) => Some(Err(error.clone().into())),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> ResultKind {
+        match self {
+            Self::Command(..)
+            | Self::Privileged(..)
+            | Self::Script(..)
+            | Self::Calc(..)
+            | Self::Shell(..)
+            | Self::Definition(..)
+            | Self::Process(..) => ResultKind::Command,
+            Self::Url(_) => ResultKind::Url,
+            Self::App(_) | Self::AppWith(..) => ResultKind::App,
+            Self::Bin(_) => ResultKind::Bin,
+            Self::File(_) => ResultKind::File,
+        }
+    }
+
+    // Path of the file the result is about
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::App(path) | Self::AppWith(path, _) | Self::Bin(path) | Self::File(path) => {
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+
+    // What the ignore list stores for the result
+    pub fn identity(&self) -> Option<&str> {
+        match self {
+            Self::Url(url) => Some(url),
+            _ => self.path(),
+        }
+    }
+
+    // `spaces` is `Config::spaces`, for opening apps on a Space
+    pub fn actions(&self, spaces: &HashMap<String, u32>) -> Vec<ResultAction> {
+        let mut actions = vec![];
+        if let Self::App(path) = self {
+            let name = Path::new(path).file_stem().unwrap_or_default();
+            if let Some(space) = spaces
+                .get(name.to_string_lossy().as_ref())
+                .or_else(|| spaces.get("*"))
+            {
+                actions.push(ResultAction {
+                    name: "open_in_space",
+                    label: format!("Open on Space {}", space),
+                    action: Action::OpenInSpace(path.to_string(), *space),
+                    confirmation: None,
+                });
+            }
+        }
+        // apps in CoreServices are mostly agents and helpers without a menu bar, and System
+        // Settings is the settings. Other apps are checked when the action runs, and those
+        // without settings not offered it again
+        if let Self::App(path) = self {
+            let name = Path::new(path).file_stem().unwrap_or_default();
+            let name = name.to_string_lossy();
+            if !path.starts_with("/System/Library/CoreServices/")
+                && !["System Settings", "System Preferences"].contains(&name.as_ref())
+                && !NO_SETTINGS.lock().unwrap().contains(path)
+            {
+                actions.push(ResultAction {
+                    name: "open_settings",
+                    label: format!("Open {} settings", name),
+                    action: Action::OpenSettings(path.to_string()),
+                    confirmation: None,
+                });
+            }
+        }
+        if let (ResultKind::App | ResultKind::Bin | ResultKind::File, Some(path)) =
+            (self.kind(), self.path())
+        {
+            let name = Path::new(path.trim_end_matches('/'))
+                .file_name()
+                .map_or(path.to_string(), |n| n.to_string_lossy().to_string());
+            actions.push(ResultAction {
+                name: "copy_path",
+                label: "Copy full path".to_string(),
+                action: Action::CopyText(path.to_string()),
+                confirmation: None,
+            });
+            actions.push(ResultAction {
+                name: "copy_filename",
+                label: "Copy filename".to_string(),
+                action: Action::CopyText(name),
+                confirmation: None,
+            });
+            actions.push(ResultAction {
+                name: "copy_escaped_path",
+                label: "Copy POSIX-escaped path".to_string(),
+                action: Action::CopyText(shell_quote(path)),
+                confirmation: None,
+            });
+        }
+        if let Self::Calc(expression, display, _) = self {
+            actions.push(ResultAction {
+                name: "copy_line",
+                label: "Copy expression and result".to_string(),
+                action: Action::CopyText(format!("{} = {}", expression, display)),
+                confirmation: None,
+            });
+        }
+        if let (ResultKind::App | ResultKind::File, Some(path)) = (self.kind(), self.path()) {
+            if !path.starts_with("/System/") {
+                actions.push(ResultAction {
+                    name: "trash",
+                    label: "Move to Trash".to_string(),
+                    action: Action::Trash(path.to_string()),
+                    confirmation: Some(format!("Move `{}` to Trash? [y/N]", path)),
+                });
+                // the new name or directory is typed in the input field
+                actions.push(ResultAction {
+                    name: "rename",
+                    label: "Rename…".to_string(),
+                    action: Action::Rename(path.to_string(), String::new()),
+                    confirmation: None,
+                });
+                actions.push(ResultAction {
+                    name: "move",
+                    label: "Move to…".to_string(),
+                    action: Action::Move(path.to_string(), String::new()),
+                    confirmation: None,
+                });
+            }
+        }
+        if let Self::Process(desc, Some(pid), signal) = self {
+            actions.push(ResultAction {
+                name: "kill",
+                label: format!("Send SIG{}", signal),
+                action: Action::Kill(*pid, signal.clone()),
+                confirmation: Some(format!("Send SIG{} to {}? [y/N]", signal, desc)),
+            });
+        }
+        let text = self.text();
+        if !text.is_empty() {
+            actions.push(ResultAction {
+                name: "insert",
+                label: "Insert into the app in front".to_string(),
+                action: Action::Insert(text),
+                confirmation: None,
+            });
+        }
+        if let Some(identity) = self.identity() {
+            actions.push(ResultAction {
+                name: "ignore",
+                label: "Hide from results".to_string(),
+                action: Action::Ignore(identity.to_string()),
+                confirmation: Some(format!("Always hide `{}` from results? [y/N]", identity)),
+            });
+        }
+        actions
+    }
+
+    // The action `enter` (`Config::enter`) gives Enter on this result, if it applies to it.
+    // `None` for the built-in default
+    pub fn enter_override(&self, enter: &HashMap<String, String>) -> Option<&'static str> {
+        let kind = self.kind();
+        let name = enter.get(kind.name())?;
+        let name = *enter_actions(kind).iter().find(|a| *a == name)?;
+        let applies = match name {
+            "open" => false,
+            "open-with" => matches!(self, Self::App(_)),
+            "reveal" | "terminal-here" => self.path().is_some(),
+            _ => true,
+        };
+        applies.then_some(name)
+    }
+
+    // The `[enter]` command for the result's kind, if that is a template rather than a named
+    // action
+    pub fn enter_template(&self, enter: &HashMap<String, String>) -> Option<Action> {
+        let template = enter.get(self.kind().name())?;
+        is_template(template).then(|| Action::Run(fill_template(template, self)))
+    }
+
+    // Action run by Enter for `enter_override`, except for `open-with` which picks a file first
+    pub fn enter_action(&self, name: &str) -> Option<Action> {
+        match name {
+            "copy" => Some(Action::CopyText(self.text())),
+            "insert" => Some(Action::Insert(self.text())),
+            "reveal" => Some(Action::Reveal(self.path()?.to_string())),
+            "terminal-here" => {
+                let path = Path::new(self.path()?);
+                let dir = if path.is_dir() { path } else { path.parent()? };
+                Some(Action::TerminalHere(dir.to_string_lossy().to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    // What Enter does, shown next to the query
+    pub fn enter_label(&self, enter: &HashMap<String, String>) -> &'static str {
+        if self.enter_template(enter).is_some() {
+            return "Run";
+        }
+        match self.enter_override(enter) {
+            Some("copy") => return "Copy",
+            Some("insert") => return "Insert",
+            Some("reveal") => return "Reveal in Finder",
+            Some("open-with") => return "Open with",
+            Some("terminal-here") => return "Terminal here",
+            _ => {}
+        }
+        match self {
+            Self::Command(cmd, _) if cmd == "search" => "Search",
+            Self::Command(..) | Self::Bin(_) | Self::Script(..) | Self::Shell(..) => "Run",
+            Self::Privileged(..) => "Run as root",
+            Self::Process(..) => "Kill",
+            Self::Url(url) if url.starts_with("mailto:") => "Compose email",
+            Self::Url(_) | Self::App(_) | Self::File(_) => "Open",
+            Self::AppWith(..) => "Open with",
+            Self::Calc(..) | Self::Definition(..) => "Copy",
+        }
+    }
+
+    // What the `copy` and `insert` actions take from the result
+    pub fn text(&self) -> String {
+        match self {
+            Self::Calc(_, _, value) | Self::Definition(_, value) => value.clone(),
+            Self::Url(url) => url.clone(),
+            _ => self.path().map_or_else(|| self.get_content(), String::from),
+        }
+    }
+
+    // What Enter does instead of selecting the result, so the launcher stays open
+    pub fn accept_action(&self) -> Option<Action> {
+        match self {
+            Self::Calc(_, _, value) | Self::Definition(_, value) => {
+                Some(Action::CopyText(value.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    // Called after `accept_action` ran, e.g. to make the copied value the calculator's `ans`.
+    // What is remembered is the value before `calc_copy_digits` rounded it: the expression's
+    // again, or that of the same calculation in the history when it was listed from there
+    pub fn accepted(&self, config: &Config) {
+        if let Self::Calc(expression, _, value) = self {
+            let file = config.calc_history_file.as_deref();
+            let copied = |v: &f64| calc::format(*v, config.calc_copy_digits) == *value;
+            let exact = calc::eval(expression, &calc::vars(file))
+                .and_then(Result::ok)
+                .filter(copied)
+                .or_else(|| {
+                    calc::history(file)
+                        .into_iter()
+                        .find(|(e, v)| e == expression && copied(v))
+                        .map(|(_, v)| v)
+                })
+                .or_else(|| value.parse().ok());
+            if let Some(value) = exact {
+                let save = !is_private(expression, config);
+                calc::remember(expression, value, file, config.history_dedup, save);
+            }
+        }
+    }
+
+    // Code the result runs and its language as a file extension, for the preview pane
+    pub fn snippet(&self) -> Option<(&str, &'static str)> {
+        match self {
+            Self::Shell(_, cmd, _) | Self::Privileged(_, cmd) => Some((cmd, "sh")),
+            Self::Command(cmd, param) if cmd == "exec" && !param.is_empty() => Some((param, "sh")),
+            Self::Script(_, script) => Some((script, "applescript")),
+            _ => None,
+        }
+    }
+
+    // Message shown before the result is run. `None` if it runs right away
+    pub fn confirmation(&self) -> Option<String> {
+        match self {
+            Self::Privileged(desc, _) => Some(format!(
+                "Run `{}` with administrator privileges? [y/N]",
+                desc
+            )),
+            Self::Process(desc, Some(_), signal) => {
+                Some(format!("Send SIG{} to {}? [y/N]", signal, desc))
+            }
+            _ => None,
+        }
+    }
+
+    fn prerun_command(self, _cache: &Cache) -> io::Result<Vec<LauncherResult>> {
         if let LauncherResult::Command(cmd, param) = &self {
+            if let Some(shell) = privileged_command(cmd, param) {
+                let desc = format!("{} {}", cmd, param).trim().to_string();
+                return Ok(vec![LauncherResult::Privileged(desc, shell)]);
+            }
             match cmd.as_str() {
                 "find" => {
                     // BFS file directory
@@ -130,6 +1245,38 @@ impl LauncherResult {
         }
     }
 
+    // The string without the kind label, used for completion and copying
+    pub fn get_content(&self) -> String {
+        let s = self.get_string();
+        s.split_once('|')
+            .map_or(s.as_str(), |(_, c)| c)
+            .trim()
+            .to_string()
+    }
+
+    // The result as a name and a detail, for `detail_column`: files and their directory,
+    // calculations and their value. `None` for the others
+    pub fn name_and_detail(&self) -> Option<(String, String)> {
+        match self {
+            Self::App(path) | Self::Bin(path) | Self::File(path) => {
+                let path = Path::new(path.trim_end_matches('/'));
+                let name = path.file_name()?.to_string_lossy();
+                let dir = path.parent()?.to_string_lossy();
+                let dir = match dir.strip_prefix(HOME_PATH.as_str()) {
+                    Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+                    _ => dir.to_string(),
+                };
+                let kind = self.get_string();
+                let kind = kind.split_once('|')?.0;
+                Some((format!("{}| {}", kind, name), dir))
+            }
+            Self::Calc(expression, display, value) if !value.is_empty() => {
+                Some((format!("Calc | {}", expression), format!("= {}", display)))
+            }
+            _ => None,
+        }
+    }
+
     pub fn get_string(&self) -> String {
         match self {
             LauncherResult::Command(cmd, param) => format!("Cmd  | :{} {}", cmd, param),
@@ -137,6 +1284,32 @@ impl LauncherResult {
             LauncherResult::App(app) => format!("App  | {}", app),
             LauncherResult::Bin(bin) => format!("Bin  | {}", bin),
             LauncherResult::File(file) => format!("File | {}", file),
+            LauncherResult::Privileged(desc, _) => format!("Sudo | :{}", desc),
+            LauncherResult::Process(desc, ..) => format!("Proc | {}", desc),
+            LauncherResult::Script(desc, _) => format!("Scpt | {}", desc),
+            LauncherResult::Shell(desc, ..) => format!("Cmd  | :{}", desc),
+            LauncherResult::Definition(word, definition) => format!(
+                "Dict | {}: {}",
+                word,
+                definition
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+            ),
+            LauncherResult::Calc(expression, error, value) if value.is_empty() => {
+                format!("Calc | {}: {}", expression, error)
+            }
+            LauncherResult::Calc(expression, display, _) => {
+                format!("Calc | {} = {}", expression, display)
+            }
+            LauncherResult::AppWith(app, args) => format!(
+                "App  | Open {} with '{}'",
+                Path::new(app)
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                args
+            ),
         }
     }
 }
@@ -153,6 +1326,8 @@ pub struct FileEntry {
     file_type: FileEntryType,
     full_path: String,
     name: String,
+    romaji: Option<String>, // of a name with kana, see `Config::kana_romaji`
+    modified: Option<SystemTime>, // for ordering equally scored entries
 }
 
 impl Hash for FileEntry {
@@ -161,10 +1336,45 @@ impl Hash for FileEntry {
     }
 }
 
+// Numbers the search rounds, one per query typed. Searches of an older round are stale
+#[derive(Debug, Clone, Default)]
+pub struct Generations(Arc<AtomicU64>);
+
+impl Generations {
+    // Starts a round, making the ones before stale
+    pub fn next(&self) -> Round {
+        Round {
+            generation: self.0.fetch_add(1, AtomicOrdering::SeqCst) + 1,
+            current: Arc::clone(&self.0),
+        }
+    }
+}
+
+// Carried by a search, checked before its results are kept
+#[derive(Debug, Clone)]
+pub struct Round {
+    generation: u64,
+    current: Arc<AtomicU64>,
+}
+
+impl Round {
+    // Never stale, for searches that can't be superseded
+    pub fn none() -> Round {
+        Generations::default().next()
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.current.load(AtomicOrdering::SeqCst) != self.generation
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Cache {
     pub file_entries: HashSet<Arc<FileEntry>>,
     pub search_results: HashMap<String, Arc<Vec<LauncherResult>>>,
+    pub top_hits: HashSet<String>, // keys whose first result is the top hit
+    pub searched: HashMap<String, Instant>, // when the results of a key were found
+    pub expires: HashMap<String, Instant>, // `get_string` of results dropped after then, see `prune`
 }
 
 macro_rules! into_string {
@@ -178,6 +1388,9 @@ impl Cache {
         return Cache {
             file_entries: HashSet::new(),
             search_results: HashMap::new(),
+            top_hits: HashSet::new(),
+            searched: HashMap::new(),
+            expires: HashMap::new(),
         };
     }
 
@@ -194,13 +1407,15 @@ impl Cache {
             full_path
         };
         let name = if let Some(name) = parent.file_name() {
-            into_string!(name) + "/"
+            into_string!(name).nfc().collect::<String>() + "/"
         } else {
             String::from("/")
         };
         FileEntry {
             file_type: FileEntryType::File,
+            modified: fs::metadata(&full_path).and_then(|m| m.modified()).ok(),
             full_path,
+            romaji: kana::to_romaji(&name),
             name,
         }
     }
@@ -221,11 +1436,14 @@ impl Cache {
                 for path in dir {
                     let path = path.unwrap();
 
-                    let name = into_string!(path.file_name());
+                    // matched against normalized queries, macOS names are often decomposed
+                    let name: String = into_string!(path.file_name()).nfc().collect();
                     self.file_entries.insert(Arc::new(FileEntry {
                         file_type: r#type,
                         full_path: into_string!(path.path()),
+                        romaji: kana::to_romaji(&name),
                         name,
+                        modified: path.metadata().and_then(|m| m.modified()).ok(),
                     }));
                 }
             }
@@ -233,48 +1451,213 @@ impl Cache {
     }
 
     pub fn init(config: &Config) -> Cache {
+        let _searching = Searching::start("index");
         let mut cache = Cache::new();
-        cache.add_dir(&config.app_locations, FileEntryType::App);
+        cache.add_dir(
+            config.app_locations.iter().map(|l| expand_path(l)),
+            FileEntryType::App,
+        );
         cache.add_dir(
             &env::var("PATH").unwrap().split(':').collect::<Vec<&str>>(),
             FileEntryType::Bin,
         );
         cache.add_dir(&[HOME_PATH.to_string()], FileEntryType::File);
+        cache.file_entries.retain(|e| !is_ignored(&e.full_path));
         return cache;
     }
 
-    pub fn get_results(&self, query: &str) -> Option<Arc<Vec<LauncherResult>>> {
-        if self.search_results.contains_key(query) {
-            return Some(self.search_results[query].clone());
-        } else {
-            None
+    // Forget a file that no longer exists or was hidden, and everything inside it.
+    // Also takes URLs
+    pub fn remove_path(&mut self, path: &str) {
+        let removed = |p: &str| within(p, path);
+        self.file_entries.retain(|e| !removed(&e.full_path));
+        for (key, results) in self.search_results.iter_mut() {
+            if results
+                .first()
+                .and_then(|r| r.identity())
+                .is_some_and(removed)
+            {
+                self.top_hits.remove(key);
+            }
+            if results.iter().any(|r| r.identity().is_some_and(removed)) {
+                *results = Arc::new(
+                    results
+                        .iter()
+                        .filter(|r| !r.identity().is_some_and(removed))
+                        .cloned()
+                        .collect(),
+                );
+            }
+        }
+    }
+
+    pub fn get_results(&self, query: &str) -> Option<Arc<Vec<LauncherResult>>> {
+        if self.search_results.contains_key(query) {
+            return Some(self.search_results[query].clone());
+        } else {
+            None
+        }
+    }
+
+    // Forget results that may have changed since, see `refresh_interval`, so the query is
+    // searched again. Returns whether they were
+    pub fn expire(&mut self, query: &str, interval: Duration) -> bool {
+        if self
+            .searched
+            .get(query)
+            .is_some_and(|t| t.elapsed() >= interval)
+        {
+            self.searched.remove(query);
+            self.search_results.remove(query);
+            self.top_hits.remove(query);
+            return true;
+        }
+        false
+    }
+
+    pub fn is_top_hit(&self, query: &str) -> bool {
+        self.top_hits.contains(query)
+    }
+
+    // Keeps what `Query::parse` found, unless the query changed since. Returns whether it did
+    pub fn merge(&mut self, delta: Cache, round: &Round) -> bool {
+        if round.is_stale() {
+            return false;
+        }
+        self.file_entries.extend(delta.file_entries);
+        self.search_results.extend(delta.search_results);
+        self.top_hits.extend(delta.top_hits);
+        self.searched.extend(delta.searched);
+        self.expires.extend(delta.expires);
+        true
+    }
+
+    // `result` is dropped from every list by `prune` once `at` has passed, e.g. a one-time
+    // code. Results don't expire otherwise
+    pub fn expire_at(&mut self, result: &LauncherResult, at: Instant) {
+        self.expires.insert(result.get_string(), at);
+    }
+
+    // Drops the results that expired by `now`, returns the `get_string` of those there were.
+    // Expiries are kept, a search finding one again has it dropped on the next call
+    pub fn prune(&mut self, now: Instant) -> HashSet<String> {
+        let expired = self
+            .expires
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(s, _)| s)
+            .collect::<HashSet<&String>>();
+        let mut pruned = HashSet::new();
+        if expired.is_empty() {
+            return pruned;
+        }
+        let is_expired = |r: &LauncherResult| expired.contains(&r.get_string());
+        for (key, results) in self.search_results.iter_mut() {
+            if results.first().is_some_and(is_expired) {
+                self.top_hits.remove(key);
+            }
+            if results.iter().any(is_expired) {
+                let (gone, kept) = results.iter().cloned().partition::<Vec<_>, _>(is_expired);
+                pruned.extend(gone.iter().map(|r| r.get_string()));
+                *results = Arc::new(kept);
+            }
         }
+        pruned
     }
 
-    pub fn add_results(&mut self, query: &str, results: Vec<LauncherResult>) {
+    // Hidden results are left out, whichever search found them
+    pub fn add_results(&mut self, query: &str, mut results: Vec<LauncherResult>) {
+        results.retain(|r| !r.identity().is_some_and(is_ignored));
+        self.searched.insert(query.to_string(), Instant::now());
         self.search_results
             .insert(query.to_string(), Arc::new(results));
     }
 
-    fn search(&self, query: &str, kind: &str, config: &Config) -> Vec<LauncherResult> {
-        let mut results: Vec<LauncherResult> = vec![];
+    // App `word` names, see `app_named`. One named exactly that first, then the shortest name
+    fn find_app(&self, word: &str) -> Option<Arc<FileEntry>> {
+        self.file_entries
+            .iter()
+            .filter(|e| e.file_type == FileEntryType::App)
+            .filter_map(|e| Some((app_named(&e.name, word)?, e)))
+            .min_by_key(|(exact, e)| (!exact, e.name.len()))
+            .map(|(_, e)| e.clone())
+    }
 
-        let fuzzy_search_results: Vec<Arc<FileEntry>> = match kind {
+    // Every entry, apps first
+    fn all(&self) -> Vec<LauncherResult> {
+        let mut entries = self.file_entries.iter().collect::<Vec<&Arc<FileEntry>>>();
+        entries.sort_unstable_by_key(|e| (e.file_type as u8, e.name.to_lowercase()));
+        entries
+            .into_iter()
+            .map(|e| match e.file_type {
+                FileEntryType::App => LauncherResult::App(e.full_path.clone()),
+                FileEntryType::Bin => LauncherResult::Bin(e.full_path.clone()),
+                FileEntryType::File => LauncherResult::File(e.full_path.clone()),
+            })
+            .collect()
+    }
+
+    // `only` restricts the search to one kind of entries. The results come with their scores,
+    // higher is better. Also whether the first result is a top hit: confident enough and
+    // ahead of the second by `top_hit_margin`, skim only
+    fn search(
+        &self,
+        query: &str,
+        kind: &str,
+        only: Option<FileEntryType>,
+        config: &Config,
+    ) -> (Vec<(i64, LauncherResult)>, bool) {
+        let mut results: Vec<(i64, LauncherResult)> = vec![];
+        let mut top_hit = false;
+        let frecency = match config.tie_break.values().any(|t| t == "frecency") {
+            true => learned::frecency(config.learn_half_life_days),
+            false => HashMap::new(),
+        };
+
+        let fuzzy_search_results: Vec<(i64, Arc<FileEntry>)> = match kind {
             "skim" => {
-                let skim = SkimMatcherV2::default();
+                let skim = config.matcher.skim();
+                // kana in the query also match romaji names
+                let romaji_query = kana::to_romaji(query).filter(|_| config.kana_romaji);
+                let queries = [Some(query), romaji_query.as_deref()];
                 let mut fuzzy_search_results = self
                     .file_entries
                     .par_iter()
+                    .filter(|x| only.is_none_or(|t| t == x.file_type))
                     .filter_map(|x| {
-                        let (score, indices) = skim.fuzzy_indices(&x.name, query)?;
-                        let coverage = indices.len() * 1024 / x.name.len();
+                        let romaji = x.romaji.as_deref().filter(|_| config.kana_romaji);
+                        let names = [Some(x.name.as_str()), romaji];
+                        let (score, coverage) = names
+                            .iter()
+                            .flatten()
+                            .flat_map(|name| queries.iter().flatten().map(move |q| (name, q)))
+                            .filter_map(|(name, q)| {
+                                let (score, indices) = skim.fuzzy_indices(name, q)?;
+                                Some((score, indices.len() * 1024 / name.len()))
+                            })
+                            .max()?;
                         Some((score, coverage, Arc::clone(&x)))
                     })
                     .collect::<Vec<(i64, usize, Arc<FileEntry>)>>();
-                fuzzy_search_results.sort_unstable_by_key(|e| (Reverse(e.0), Reverse(e.1)));
+                fuzzy_search_results.sort_unstable_by(|a, b| {
+                    (Reverse(a.0), Reverse(a.1))
+                        .cmp(&(Reverse(b.0), Reverse(b.1)))
+                        .then_with(|| config.tie_break(&a.2, &b.2, &frecency))
+                });
+                // full coverage is worth two matched characters
+                let combined =
+                    |(score, coverage, _): &(i64, usize, _)| score + *coverage as i64 / 32;
+                let mut scores = fuzzy_search_results.iter().map(combined);
+                top_hit = match (scores.next(), scores.next()) {
+                    (Some(first), second) => {
+                        first >= config.top_hit_min_score * query.chars().count() as i64
+                            && second.is_none_or(|s| first - s >= config.top_hit_margin)
+                    }
+                    _ => false,
+                };
                 fuzzy_search_results
                     .iter()
-                    .map(|e| Arc::clone(&e.2))
+                    .map(|e| (combined(e), Arc::clone(&e.2)))
                     .collect()
             }
 
@@ -289,6 +1672,7 @@ impl Cache {
                 let mut fuzzy_search_results = self
                     .file_entries
                     .par_iter()
+                    .filter(|x| only.is_none_or(|t| t == x.file_type))
                     .filter_map(|x| {
                         if query.len() <= x.name.len() {
                             let result = fuse.search(pattern.as_ref(), &x.name)?;
@@ -306,10 +1690,15 @@ impl Cache {
                         }
                     })
                     .collect::<Vec<(i64, usize, Arc<FileEntry>)>>();
-                fuzzy_search_results.sort_unstable_by_key(|e| (e.0, e.1));
+                fuzzy_search_results.sort_unstable_by(|a, b| {
+                    (a.0, a.1)
+                        .cmp(&(b.0, b.1))
+                        .then_with(|| config.tie_break(&a.2, &b.2, &frecency))
+                });
+                // fuse scores lower for better matches
                 fuzzy_search_results
                     .iter()
-                    .map(|e| Arc::clone(&e.2))
+                    .map(|e| (-e.0, Arc::clone(&e.2)))
                     .collect()
             }
             _ => {
@@ -323,48 +1712,189 @@ impl Cache {
             config.results_len
         };
         // FIXME: does it change order?
-        results.par_extend(fuzzy_search_results[0..end_index].par_iter().map(
-            |r| match r.file_type {
-                FileEntryType::App => LauncherResult::App(r.full_path.clone()),
-                FileEntryType::Bin => LauncherResult::Bin(r.full_path.clone()),
-                FileEntryType::File => LauncherResult::File(r.full_path.clone()),
-            },
-        ));
-        return results;
+        results.par_extend(
+            fuzzy_search_results[0..end_index]
+                .par_iter()
+                .map(|(score, r)| {
+                    let result = match r.file_type {
+                        FileEntryType::App => LauncherResult::App(r.full_path.clone()),
+                        FileEntryType::Bin => LauncherResult::Bin(r.full_path.clone()),
+                        FileEntryType::File => LauncherResult::File(r.full_path.clone()),
+                    };
+                    (*score, result)
+                }),
+        );
+        (results, top_hit && config.top_hit)
     }
 }
 
-pub struct Query(String);
+// Which results a query looks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    All,
+    OpenWith(String),    // files to open with the app at this path
+    Only(FileEntryType), // one kind of entries, see `Config::modes`
+}
+
+impl Scope {
+    // Scope of a mode in `Config::modes`
+    pub fn from_mode(mode: &str) -> Option<Scope> {
+        match mode {
+            "all" => Some(Scope::All),
+            "apps" => Some(Scope::Only(FileEntryType::App)),
+            "bins" => Some(Scope::Only(FileEntryType::Bin)),
+            "files" => Some(Scope::Only(FileEntryType::File)),
+            _ => None,
+        }
+    }
+
+    // Key of the query's results in `Cache::search_results`
+    pub fn key(&self, query: &str) -> String {
+        match self {
+            Scope::All => query.to_string(),
+            Scope::OpenWith(app) => format!("{}\0{}", app, query),
+            Scope::Only(kind) => format!("{:?}\0{}", kind, query),
+        }
+    }
+
+    // Its level in the breadcrumb
+    pub fn title(&self) -> String {
+        match self {
+            Scope::All => "All".to_string(),
+            Scope::OpenWith(app) => {
+                let name = Path::new(app).file_stem().unwrap_or_default();
+                format!("Open with {}", name.to_string_lossy())
+            }
+            Scope::Only(FileEntryType::App) => "Apps".to_string(),
+            Scope::Only(FileEntryType::Bin) => "Bins".to_string(),
+            Scope::Only(FileEntryType::File) => "Files".to_string(),
+        }
+    }
+
+    // What selecting `result` in this scope does
+    pub fn apply(&self, result: LauncherResult) -> LauncherResult {
+        match (self, result) {
+            (Scope::OpenWith(app), LauncherResult::File(path)) => {
+                LauncherResult::AppWith(app.clone(), shell_quote(&path))
+            }
+            (_, result) => result,
+        }
+    }
+}
+
+// Prompt for the provider the query's keyword selects, if any
+pub fn mode_prompt(query: &str, config: &Config) -> Option<String> {
+    let keyword = |k: &str| {
+        !k.is_empty()
+            && query
+                .strip_prefix(k)
+                .is_some_and(|rest| rest.starts_with(' '))
+    };
+    if query.starts_with(':') {
+        Some("Command>".to_string())
+    } else if query.trim() == "=" || query.trim() == "calc history" {
+        Some("History>".to_string())
+    } else if keyword(&config.capture_keyword) {
+        Some(match config.capture_app.as_str() {
+            "Notes" => "Note>".to_string(),
+            _ => "Remind>".to_string(),
+        })
+    } else if keyword(&config.define_keyword) {
+        Some("Define>".to_string())
+    } else if keyword(&config.email_keyword) || query.trim() == config.email_keyword {
+        Some("Email>".to_string())
+    } else if keyword(&config.menu_keyword) || query.trim() == config.menu_keyword {
+        Some("Menu>".to_string())
+    } else {
+        None
+    }
+}
+
+pub struct Query(String, Scope);
 
 impl Query {
     pub fn new() -> Query {
-        Query(String::new())
+        Query(String::new(), Scope::All)
     }
 
     pub fn from(s: &str) -> Query {
-        Query(s.to_string())
+        Query(s.to_string(), Scope::All)
+    }
+
+    pub fn with_scope(s: &str, scope: Scope) -> Query {
+        Query(s.to_string(), scope)
     }
 
     // return new Cache entries only
     pub fn parse(&self, config: &Config, cache: Cache) -> io::Result<Cache> {
+        self.parse_in(config, cache, &Round::none())
+    }
+
+    // Like `parse`, stopping with nothing found between sources once `round` is stale
+    pub fn parse_in(&self, config: &Config, cache: Cache, round: &Round) -> io::Result<Cache> {
         let mut delta = Cache::new();
 
         let query = self.0.trim();
+        let key = self.1.key(query);
+        if cache.get_results(&key).is_some() {
+            return Ok(delta);
+        }
+        let mut results: Vec<LauncherResult> = vec![];
+
         if query.is_empty() {
+            if self.1 == Scope::All {
+                match config.empty_query.as_str() {
+                    "all" => results = cache.all(),
+                    "recent" => results = recent(),
+                    _ => {}
+                }
+            }
+            delta.add_results(&key, results);
             return Ok(delta);
         }
 
-        if cache.get_results(query).is_some() {
+        let only = match self.1 {
+            Scope::OpenWith(_) => Some(FileEntryType::File),
+            Scope::Only(kind) => Some(kind),
+            Scope::All => None,
+        };
+        if let Some(kind) = only {
+            if query.len() < 15 {
+                let (found, top_hit) =
+                    cache.search(query, &config.fuzzy_engine, Some(kind), config);
+                if top_hit {
+                    delta.top_hits.insert(key.clone());
+                }
+                results.extend(found.into_iter().map(|(_, r)| r));
+            }
+            if kind == FileEntryType::File && query.contains('/') {
+                results.extend(list_dir(&expand_path(query), config.results_len));
+            }
+            delta.add_results(&key, results);
             return Ok(delta);
         }
-        let mut results: Vec<LauncherResult> = vec![];
 
         // History
         // TODO: save search queries, exec commands
 
         // Command
         if let Some(stripped) = query.strip_prefix(':') {
-            if let Some((cmd, param)) = stripped.trim().split_once(' ') {
+            let (cmd, param) = stripped
+                .trim()
+                .split_once(' ')
+                .unwrap_or((stripped.trim(), ""));
+            if let Some(command) = config.commands.get(cmd.trim()) {
+                let run = format!("{} {}", command.run, param.trim());
+                results.push(LauncherResult::Shell(
+                    format!("{} {}", cmd.trim(), param.trim())
+                        .trim()
+                        .to_string(),
+                    run.trim().to_string(),
+                    command.env.clone().into_iter().collect(),
+                ));
+            } else if cmd.trim() == "port" {
+                results.extend(port_processes(param.trim(), &config.kill_signal));
+            } else if let Some((cmd, param)) = stripped.trim().split_once(' ') {
                 results.extend(
                     LauncherResult::Command(cmd.trim().to_string(), param.trim().to_string())
                         .prerun_command(&cache)?,
@@ -375,23 +1905,153 @@ impl Query {
                         .prerun_command(&cache)?,
                 );
             }
-            delta.add_results(query, results);
+            delta.add_results(&key, results);
+            return Ok(delta);
+        }
+
+        // Calculator, e.g. `2^10 / 3`, `ans * 2`. `=` lists the accepted ones
+        let history_file = config.calc_history_file.as_deref();
+        if query == "=" || query == "calc history" {
+            let history = calc::history(history_file);
+            if history.is_empty() {
+                results.push(LauncherResult::Calc(
+                    "history".to_string(),
+                    "no previous result".to_string(),
+                    String::new(),
+                ));
+            }
+            results.extend(history.into_iter().map(|(expression, value)| {
+                LauncherResult::Calc(
+                    expression,
+                    calc::format(value, Some(config.calc_digits)),
+                    calc::format(value, config.calc_copy_digits),
+                )
+            }));
+            delta.add_results(&key, results);
             return Ok(delta);
         }
+        match calc::eval(query, &calc::vars(history_file)) {
+            Some(Ok(value)) => results.push(LauncherResult::Calc(
+                query.to_string(),
+                calc::format(value, Some(config.calc_digits)),
+                calc::format(value, config.calc_copy_digits),
+            )),
+            Some(Err(e)) => results.push(LauncherResult::Calc(query.to_string(), e, String::new())),
+            None => {}
+        }
+
+        // Quick capture, e.g. `remind buy milk in 30 min`
+        if let Some(text) = query
+            .strip_prefix(config.capture_keyword.as_str())
+            .and_then(|t| t.strip_prefix(' '))
+        {
+            if let Some(r) = capture(text.trim(), config) {
+                results.push(r);
+            }
+        }
+
+        if round.is_stale() {
+            return Ok(Cache::new());
+        }
+
+        // Email, e.g. `email alice about lunch`
+        if let Some(text) = query
+            .strip_prefix(config.email_keyword.as_str())
+            .filter(|t| t.is_empty() || t.starts_with(' '))
+        {
+            if !config.email_keyword.is_empty() {
+                let (to, subject) = parse_email(text.trim(), &config.email_pattern);
+                results.push(LauncherResult::Url(mailto(&to, &subject)));
+            }
+        }
+
+        // Settings, e.g. `dark mode off`, `toggle hidden files`
+        results.extend(toggles(query, config));
+
+        // Dictionary, e.g. `define ephemeral`
+        if let Some(word) = query
+            .strip_prefix(config.define_keyword.as_str())
+            .and_then(|t| t.strip_prefix(' '))
+            .filter(|_| !config.define_keyword.is_empty())
+        {
+            let searching = Searching::start("dictionary");
+            let definition = dictionary::define(word);
+            drop(searching);
+            if let Some(definition) = definition {
+                results.push(LauncherResult::Definition(
+                    word.trim().to_string(),
+                    definition,
+                ));
+            }
+        }
+
+        // results of the providers that score them, merged by `score_normalization`
+        let mut scored: Vec<Vec<(i64, LauncherResult)>> = vec![];
+        let normalized = config.score_normalization != "none";
+
+        // Menu bar item of the frontmost app, e.g. `menu export`
+        if let Some(item) = query
+            .strip_prefix(config.menu_keyword.as_str())
+            .filter(|t| t.is_empty() || t.starts_with(' '))
+        {
+            if !config.menu_keyword.is_empty() && !round.is_stale() {
+                let _searching = Searching::start("menu");
+                let found = menu::search(item.trim(), config.results_len, &config.matcher);
+                match normalized {
+                    true => scored.push(found),
+                    false => results.extend(found.into_iter().map(|(_, r)| r)),
+                }
+            }
+        }
+
+        // App followed by arguments, e.g. `safari github.com`
+        if let Some((name, args)) = query.split_once(char::is_whitespace) {
+            if name.len() > 1 {
+                if let Some(app) = cache.find_app(name) {
+                    results.push(LauncherResult::App(app.full_path.clone()));
+                    results.push(LauncherResult::AppWith(
+                        app.full_path.clone(),
+                        args.trim().to_string(),
+                    ));
+                }
+            }
+        }
 
         // Url
         let query_clone = query.to_string();
-        let lookup_host_thread = thread::spawn(move || lookup_host(&query_clone));
+        let lookup_host_thread = thread::spawn(move || {
+            let _searching = Searching::start("url");
+            lookup_host(&query_clone)
+        });
+
+        if round.is_stale() {
+            return Ok(Cache::new());
+        }
 
         // fuzzy search app / bin / opened files
         // only search of query.len() < 15
         if query.len() < 15 {
-            results.extend(cache.search(query, &config.fuzzy_engine, config));
+            let (found, top_hit) = cache.search(query, &config.fuzzy_engine, None, config);
+            // only if nothing comes before it, e.g. a calculation
+            if top_hit && results.is_empty() && scored.iter().all(Vec::is_empty) {
+                delta.top_hits.insert(key.clone());
+            }
+            scored.push(found);
+        }
+        results.extend(merge_scored(scored, &config.score_normalization));
+        // the app found for its arguments isn't listed again by the fuzzy search
+        let mut apps = HashSet::new();
+        results.retain(|r| !matches!(r, LauncherResult::App(path) if !apps.insert(path.clone())));
+
+        // Directory content, e.g. `~/Documents/pro`
+        if query.contains('/') {
+            results.extend(list_dir(&expand_path(query), config.results_len));
         }
 
         // File path
-        if Path::new(query).exists() {
-            results.push(LauncherResult::File(query.to_string()));
+        let expanded = expand_path(query);
+        if Path::new(&expanded).exists() {
+            results.push(LauncherResult::File(expanded));
         }
         // Relative to $HOME directory
         let relative = HOME_PATH.clone() + "/" + query;
@@ -399,6 +2059,10 @@ impl Query {
             results.push(LauncherResult::File(relative));
         }
 
+        // the lookup can't be stopped, it finishes on its own
+        if round.is_stale() {
+            return Ok(Cache::new());
+        }
         if let Ok(Ok(_)) = lookup_host_thread.join() {
             results.push(LauncherResult::Url(Self::fix_url(query)));
         }
@@ -408,7 +2072,14 @@ impl Query {
             query.to_string(),
         ));
 
-        delta.add_results(query, results);
+        // what was picked for the query before comes first, instead of the top hit
+        if config.learn_selections
+            && learned::boost(query, &mut results, config.learn_half_life_days)
+        {
+            delta.top_hits.remove(&key);
+        }
+
+        delta.add_results(&key, results);
         return Ok(delta);
     }
 
@@ -421,6 +2092,19 @@ impl Query {
     }
 }
 
+// Whether the app file `name`, e.g. "Visual Studio Code.app", is named `word` ("visual studio
+// code", case aside) or has it as one of its words ("code"). `Some(true)` for the whole name
+pub fn app_named(name: &str, word: &str) -> Option<bool> {
+    let name = name.strip_suffix(".app").unwrap_or(name).to_lowercase();
+    let word = word.to_lowercase();
+    if name == word {
+        return Some(true);
+    }
+    name.split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .any(|w| w == word)
+        .then_some(false)
+}
+
 pub fn new_magic_cookie() -> Result<Magic, FileMagicError> {
     let magic_flags = vec![
         Flags::NO_CHECK_APPTYPE,
@@ -438,8 +2122,1270 @@ pub fn new_magic_cookie() -> Result<Magic, FileMagicError> {
     return Ok(cookie);
 }
 
+// First line of the clipboard's text and how many lines follow it. `None` for binary
+// content or more than `max_len` bytes
+pub fn read_clipboard(max_len: usize) -> Option<(String, usize)> {
+    let output = Command::new("pbpaste").output().ok()?;
+    if output.stdout.len() > max_len || output.stdout.contains(&0) {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next()?.to_string();
+    Some((first, lines.count()))
+}
+
+pub fn copy_to_clipboard(s: &str) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new("pbcopy").stdin(Stdio::piped()).spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or("failed to open pbcopy's stdin")?
+        .write_all(s.as_bytes())?;
+    if !child.wait()?.success() {
+        return Err("failed to copy to the clipboard".into());
+    }
+    Ok(())
+}
+
+// Opens a Quick Look preview window, or Preview.app if `previewer` is 'preview'.
+// Does not wait for it to be closed. Its stderr goes to the log
+pub fn quick_look(path: &str, previewer: &str) -> Result<Child, Box<dyn Error>> {
+    let (program, mut command) = if previewer == "preview" {
+        let mut command = Command::new("open");
+        command.arg("-a").arg("Preview");
+        ("Preview: `open`", command)
+    } else {
+        let mut command = Command::new("qlmanage");
+        command.arg("-p");
+        ("Quick Look: `qlmanage`", command)
+    };
+    let mut child = match command
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(format!("{} not found", program).into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(stderr) = child.stderr.take() {
+        thread::spawn(move || {
+            for line in io::BufReader::new(stderr).lines().map_while(Result::ok) {
+                log(&format!("quick look: {}", line));
+            }
+        });
+    }
+    Ok(child)
+}
+
+// Closes the windows of `path` that `quick_look` opened in Preview.app, whose `open` exited
+// right away
+pub fn close_in_preview(path: &str) -> Result<(), Box<dyn Error>> {
+    run_applescript(&format!(
+        r#"if application "Preview" is running then
+    tell application "Preview" to close (every document whose path is {})
+end if"#,
+        applescript_string(path)
+    ))
+}
+
 fn spawn_process(s: &str) -> io::Result<Child> {
-    return Command::new("bash").arg("-l").arg("-c").arg(s).spawn();
+    spawn_process_with_env(s, &[])
+}
+
+// `env` is added to the inherited environment, replacing variables of the same name. Values
+// are expanded first, so `PATH = "$HOME/bin:$PATH"` extends the launcher's PATH. The login
+// profile runs afterwards and can still override them
+fn spawn_process_with_env(s: &str, env: &[(String, String)]) -> io::Result<Child> {
+    Command::new("bash")
+        .arg("-l")
+        .arg("-c")
+        .arg(s)
+        .envs(env.iter().map(|(k, v)| (k, expand_vars(v))))
+        .spawn()
+}
+
+// Split `s` into words like a shell would, honouring quotes and backslashes
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (None, '\\') | (Some('"'), '\\') => {
+                if let Some(next) = chars.next() {
+                    arg.push(next);
+                }
+                in_arg = true;
+            }
+            (None, '\'' | '"') => {
+                quote = Some(ch);
+                in_arg = true;
+            }
+            (Some(q), _) if q == ch => quote = None,
+            (None, _) if ch.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            _ => {
+                arg.push(ch);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    args
+}
+
+// `~` and `~user` at the start of `s`
+pub fn expand_tilde(s: &str) -> String {
+    let Some(rest) = s.strip_prefix('~') else {
+        return s.to_string();
+    };
+    let (user, rest) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if user.is_empty() {
+        return HOME_PATH.clone() + rest;
+    }
+    // users' homes are all next to each other, /Users on macOS
+    match Path::new(HOME_PATH.as_str()).parent() {
+        Some(homes) if homes.join(user).is_dir() => into_string!(homes.join(user)) + rest,
+        _ => s.to_string(),
+    }
+}
+
+// `$VAR` and `${VAR}` in paths and config values, shell commands are left to the shell.
+// Undefined variables expand to nothing. `\$` is a literal dollar
+pub fn expand_vars(s: &str) -> String {
+    let mut expanded = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if chars.peek() == Some(&'$') => expanded.push(chars.next().unwrap()),
+            '$' => {
+                let mut name = String::new();
+                if chars.peek() == Some(&'{') {
+                    chars.next();
+                    for ch in chars.by_ref() {
+                        if ch == '}' {
+                            break;
+                        }
+                        name.push(ch);
+                    }
+                } else {
+                    while let Some(&ch) = chars.peek() {
+                        if !(ch.is_ascii_alphanumeric() || ch == '_') {
+                            break;
+                        }
+                        name.push(ch);
+                        chars.next();
+                    }
+                }
+                if name.is_empty() {
+                    expanded.push('$');
+                } else if let Ok(value) = env::var(&name) {
+                    expanded.push_str(&value);
+                } else {
+                    log(&format!("undefined variable `{}` in `{}`", name, s));
+                }
+            }
+            _ => expanded.push(ch),
+        }
+    }
+    expanded
+}
+
+pub fn expand_path(s: &str) -> String {
+    expand_vars(&expand_tilde(s))
+}
+
+// How often results of a query change while the launcher is open, `None` if they don't.
+// Directory listings follow files being created and removed, toggles their state once read
+pub fn refresh_interval(query: &str) -> Option<Duration> {
+    if query.contains('/') {
+        return Some(LIST_DIR_REFRESH);
+    }
+    (READING_TOGGLES.load(AtomicOrdering::Relaxed) > 0).then_some(TOGGLE_REFRESH)
+}
+
+// `results` with those of a kind together, the kinds in the order they first come. At most
+// `cap` of each but `expanded`, 0 for all. Also how many of each kind are shown, of how many
+pub fn group_results(
+    results: &[LauncherResult],
+    cap: usize,
+    expanded: Option<ResultKind>,
+) -> (Vec<LauncherResult>, Vec<(ResultKind, usize, usize)>) {
+    let mut groups: Vec<(ResultKind, Vec<&LauncherResult>)> = vec![];
+    for r in results {
+        match groups.iter_mut().find(|(kind, _)| *kind == r.kind()) {
+            Some((_, group)) => group.push(r),
+            None => groups.push((r.kind(), vec![r])),
+        }
+    }
+    let mut grouped = vec![];
+    let mut counts = vec![];
+    for (kind, group) in groups {
+        let shown = match cap {
+            0 => group.len(),
+            _ if expanded == Some(kind) => group.len(),
+            _ => group.len().min(cap),
+        };
+        grouped.extend(group[..shown].iter().map(|r| (*r).clone()));
+        counts.push((kind, shown, group.len()));
+    }
+    (grouped, counts)
+}
+
+// Selection once refreshed results replaced those of `index` and `identity`, by
+// `Config::refresh_selection`: 'id' follows the result, 'index' stays in place, 'top' goes
+// to the first
+pub fn refreshed_selection(
+    policy: &str,
+    identity: Option<&str>,
+    index: Option<usize>,
+    results: &[LauncherResult],
+) -> Option<usize> {
+    let last = results.len().checked_sub(1)?;
+    let index = index.map(|i| i.min(last));
+    match policy {
+        "index" => index,
+        "top" => Some(0),
+        _ => identity
+            .and_then(|id| results.iter().position(|r| r.identity() == Some(id)))
+            .or(index),
+    }
+}
+
+// Selection when `empty_query = "recent"` lists `len` results, by `Config::recent_select`:
+// 'first' is the last one used, 'second' the one before to switch between two. `None` to
+// leave the selection as it was, 'keep'
+pub fn recent_selection(policy: &str, len: usize) -> Option<usize> {
+    let last = len.checked_sub(1)?;
+    match policy {
+        "first" => Some(0),
+        "second" => Some(last.min(1)),
+        "keep" => None,
+        other => {
+            log(&format!(
+                "unknown recent_select `{}`, keeping the selection instead",
+                other
+            ));
+            None
+        }
+    }
+}
+
+// Unicode NFC, trimmed, and runs of whitespace outside of double quotes as one space. Queries
+// look up and key results in this form, so visually identical input finds the same. `:`
+// commands are only trimmed, their arguments go to the shell as typed
+pub fn normalize_query(query: &str) -> String {
+    if query.trim_start().starts_with(':') {
+        return query.trim().to_string();
+    }
+    let query = query.nfc().collect::<String>();
+    let mut normalized = String::with_capacity(query.len());
+    let (mut quoted, mut space) = (false, false);
+    for ch in query.trim().chars() {
+        if ch.is_whitespace() && !quoted {
+            space = true;
+            continue;
+        }
+        if space {
+            normalized.push(' ');
+            space = false;
+        }
+        if ch == '"' {
+            quoted = !quoted;
+        }
+        normalized.push(ch);
+    }
+    normalized
+}
+
+// What `filter_results` matches, computed once for the results
+pub fn filter_keys(results: &[LauncherResult]) -> Vec<String> {
+    results
+        .iter()
+        .map(|r| r.get_content().nfc().collect())
+        .collect()
+}
+
+// `results` matching `query`, best first. All of them for an empty query. `keys` are their
+// `filter_keys`
+pub fn filter_results(
+    results: &[LauncherResult],
+    keys: &[String],
+    query: &str,
+    matcher: &Matcher,
+) -> Vec<LauncherResult> {
+    let skim = matcher.skim();
+    let mut matches = results
+        .iter()
+        .zip(keys)
+        .filter_map(|(r, key)| Some((skim.fuzzy_match(key, query)?, r)))
+        .collect::<Vec<(i64, &LauncherResult)>>();
+    // stable, so equal scores keep their order
+    matches.sort_by_key(|m| Reverse(m.0));
+    matches.into_iter().map(|(_, r)| r.clone()).collect()
+}
+
+// Results of several providers by score, each provider's scores first brought to one scale
+// by `method`: "min-max" maps them to 0 to 1, "z-score" to how many standard deviations they
+// are from the provider's mean. "none" keeps the providers in order. Scores are higher for
+// better results, and equal ones keep the order of the providers
+pub fn merge_scored(
+    providers: Vec<Vec<(i64, LauncherResult)>>,
+    method: &str,
+) -> Vec<LauncherResult> {
+    let normalize = |scores: &[i64]| -> Vec<f64> {
+        let scores = scores.iter().map(|s| *s as f64).collect::<Vec<f64>>();
+        let n = scores.len() as f64;
+        match method {
+            "min-max" => {
+                let min = scores.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                // one result or all equal, as good as the best of another provider
+                match max > min {
+                    true => scores.iter().map(|s| (s - min) / (max - min)).collect(),
+                    false => vec![1.0; scores.len()],
+                }
+            }
+            "z-score" => {
+                let mean = scores.iter().sum::<f64>() / n;
+                let deviation = (scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n).sqrt();
+                match deviation > 0.0 {
+                    true => scores.iter().map(|s| (s - mean) / deviation).collect(),
+                    false => vec![0.0; scores.len()],
+                }
+            }
+            _ => vec![0.0; scores.len()],
+        }
+    };
+    let mut merged = providers
+        .into_iter()
+        .flat_map(|results| {
+            let scores = results.iter().map(|(s, _)| *s).collect::<Vec<i64>>();
+            normalize(&scores)
+                .into_iter()
+                .zip(results.into_iter().map(|(_, r)| r))
+                .collect::<Vec<(f64, LauncherResult)>>()
+        })
+        .collect::<Vec<(f64, LauncherResult)>>();
+    // stable, so "none" and unknown methods keep everything in order
+    merged.sort_by(|a, b| b.0.total_cmp(&a.0));
+    merged.into_iter().map(|(_, r)| r).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    Exact, // the query is a part of the text
+    Fuzzy,
+}
+
+// Which characters of `text` `query` matches, by char index. All of a contiguous part if
+// there is one, ignoring case, otherwise those the fuzzy matcher picked
+pub fn match_indices(
+    text: &str,
+    query: &str,
+    matcher: &Matcher,
+) -> Option<(MatchKind, Vec<usize>)> {
+    let query = query.trim();
+    if query.is_empty() {
+        return None;
+    }
+    // a char at a time, so the indices stay those of `text`
+    let lower = |s: &str| {
+        s.chars()
+            .map(|c| c.to_lowercase().next().unwrap_or(c))
+            .collect::<Vec<char>>()
+    };
+    let (text_chars, query_chars) = (lower(text), lower(query));
+    if let Some(start) = text_chars
+        .windows(query_chars.len())
+        .position(|w| w == query_chars.as_slice())
+    {
+        return Some((
+            MatchKind::Exact,
+            (start..start + query_chars.len()).collect(),
+        ));
+    }
+    let (_, indices) = matcher.skim().fuzzy_indices(text, query)?;
+    Some((MatchKind::Fuzzy, indices))
+}
+
+// What was done last, for `Input::RepeatLast`
+#[derive(Deserialize, Serialize)]
+pub struct LastAction {
+    pub result: LauncherResult,
+    pub action: Option<Action>, // run instead of selecting the result, see `Config::enter`
+}
+
+impl LastAction {
+    // The file may be gone since
+    pub fn check(&self) -> Result<(), String> {
+        match self.result.path() {
+            Some(path) if !Path::new(path).exists() => Err(format!("`{}` no longer exists", path)),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn last_action() -> Option<LastAction> {
+    state::store().load("last")
+}
+
+// Privileged commands are not repeated without asking
+pub fn save_last(
+    result: &LauncherResult,
+    action: Option<&Action>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    if let LauncherResult::Privileged(..) = result {
+        return Ok(());
+    }
+    if is_private(&result.get_string(), config) {
+        return Ok(());
+    }
+    let last = LastAction {
+        result: result.clone(),
+        action: action.cloned(),
+    };
+    state::store().save("last", &last)
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct Recent {
+    results: Vec<LauncherResult>,
+}
+
+// Recently selected results, newest first
+pub fn recent() -> Vec<LauncherResult> {
+    state::store()
+        .load::<Recent>("recent")
+        .unwrap_or_default()
+        .results
+}
+
+// Remember a selection for `empty_query = "recent"`. One-off results are left out
+pub fn add_recent(result: &LauncherResult, config: &Config) -> Result<(), Box<dyn Error>> {
+    if let LauncherResult::Privileged(..)
+    | LauncherResult::Script(..)
+    | LauncherResult::Calc(..)
+    | LauncherResult::Definition(..)
+    | LauncherResult::Process(..) = result
+    {
+        return Ok(());
+    }
+    // emails are one-off too
+    if matches!(result, LauncherResult::Url(url) if url.starts_with("mailto:")) {
+        return Ok(());
+    }
+    if result.identity().is_some_and(is_ignored) || is_private(&result.get_string(), config) {
+        return Ok(());
+    }
+    let mut results = recent();
+    results.retain(|r| r != result);
+    results.insert(0, result.clone());
+    results.truncate(MAX_RECENT);
+    state::store().save("recent", &Recent { results })
+}
+
+// Kept out of the files that remember queries and selections, see `Config::history_exclude`
+pub fn is_private(text: &str, config: &Config) -> bool {
+    let lower = text.to_lowercase();
+    config
+        .history_exclude
+        .iter()
+        .any(|k| !k.is_empty() && lower.contains(&k.to_lowercase()))
+        || (config.history_exclude_secrets && text.split_whitespace().any(looks_secret))
+}
+
+// Long and mixing kinds of characters like a password or a token. Paths mix them too
+fn looks_secret(word: &str) -> bool {
+    if word.chars().count() < 12 || word.contains('/') {
+        return false;
+    }
+    let kinds = [
+        word.chars().any(char::is_lowercase),
+        word.chars().any(char::is_uppercase),
+        word.chars().any(|c| c.is_ascii_digit()),
+        word.chars().any(|c| c.is_ascii_punctuation()),
+    ];
+    kinds.iter().filter(|k| **k).count() >= 3
+}
+
+// `path` is `entry` or inside it
+fn within(path: &str, entry: &str) -> bool {
+    let dir = entry.trim_end_matches('/').to_string() + "/";
+    path == entry || path.starts_with(&dir)
+}
+
+// Hidden by the ignore list
+pub fn is_ignored(identity: &str) -> bool {
+    IGNORED
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|entry| within(identity, entry))
+}
+
+// Entries of the ignore list, in the order they were added
+pub fn ignored() -> Vec<String> {
+    IGNORED.lock().unwrap().clone()
+}
+
+fn save_ignored(entries: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(IGNORE_PATH.as_str());
+    if let Some(p) = path.parent() {
+        fs::create_dir_all(p)?;
+    }
+    fs::write(
+        path,
+        entries.iter().map(|e| e.clone() + "\n").collect::<String>(),
+    )?;
+    Ok(())
+}
+
+// Hide a path or URL from all results from now on
+pub fn ignore(identity: &str) -> Result<(), Box<dyn Error>> {
+    let mut entries = IGNORED.lock().unwrap();
+    if !entries.iter().any(|e| e == identity) {
+        entries.push(identity.to_string());
+        save_ignored(&entries)?;
+    }
+    Ok(())
+}
+
+// Remove the entries containing `pattern` from the ignore list, returns them
+pub fn unignore(pattern: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut entries = IGNORED.lock().unwrap();
+    let (removed, kept) = entries
+        .iter()
+        .cloned()
+        .partition::<Vec<String>, _>(|e| e.contains(pattern));
+    if !removed.is_empty() {
+        save_ignored(&kept)?;
+        *entries = kept;
+    }
+    Ok(removed)
+}
+
+// Quote `s` for a POSIX shell
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+// Entries of the directory part of `path` whose names start with its last component.
+// Relative paths are relative to $HOME
+fn list_dir(path: &str, len: usize) -> Vec<LauncherResult> {
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        HOME_PATH.clone() + "/" + path
+    };
+    let (dir, prefix) = path.rsplit_once('/').unwrap_or(("", &path));
+    let prefix = prefix.to_lowercase();
+    let mut names = match fs::read_dir(if dir.is_empty() { "/" } else { dir }) {
+        Ok(entries) => entries
+            .filter_map(|e| Some(into_string!(e.ok()?.file_name())))
+            .filter(|name| {
+                let lower = name.nfc().collect::<String>().to_lowercase();
+                lower.starts_with(&prefix)
+                    && lower != prefix
+                    && (prefix.starts_with('.') || !name.starts_with('.'))
+            })
+            .collect::<Vec<String>>(),
+        Err(_) => return vec![],
+    };
+    names.sort_unstable();
+    names
+        .into_iter()
+        .take(len)
+        .map(|name| LauncherResult::File(format!("{}/{}", dir, name)))
+        .collect()
+}
+
+// Arguments for `open -a <app>`. Files and urls are opened by the app,
+// everything from the first flag on is passed to the app with `--args`
+fn open_arguments(args: &str) -> Vec<String> {
+    let mut open_args = vec![];
+    let mut words = split_args(args).into_iter();
+    for word in words.by_ref() {
+        if word.starts_with('-') {
+            open_args.push("--args".to_string());
+            open_args.push(word);
+            break;
+        }
+        let path = expand_path(&word);
+        if Path::new(&path).exists() {
+            open_args.push(path);
+        } else if Url::parse(&word).is_ok() {
+            open_args.push(word);
+        } else if word.contains('.') && !word.contains('/') {
+            open_args.push(Query::fix_url(&word));
+        } else {
+            open_args.push(path);
+        }
+    }
+    open_args.extend(words);
+    open_args
+}
+
+// Commands that need root. Returns the shell command to run
+fn privileged_command(cmd: &str, param: &str) -> Option<String> {
+    match (cmd, param) {
+        ("purge", "") => Some("purge".to_string()),
+        ("flushdns", "") => Some("dscacheutil -flushcache; killall -HUP mDNSResponder".to_string()),
+        ("firewall", "on" | "off") => Some(format!(
+            "/usr/libexec/ApplicationFirewall/socketfilterfw --setglobalstate {}",
+            param
+        )),
+        ("sudo", _) if !param.is_empty() => Some(param.to_string()),
+        _ => None,
+    }
+}
+
+// Splits a trailing `in 10 min`, `at 5pm` or `tomorrow [at 9:30]` off `text`.
+// Returns the text, the AppleScript setting `d` to that date, and its description
+fn parse_when(text: &str) -> (String, Option<(String, String)>) {
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+    let rest = |n: usize| words[..words.len() - n].join(" ");
+    let n = words.len();
+    if n >= 3 && words[n - 3] == "in" {
+        if let Ok(amount) = words[n - 2].parse::<u32>() {
+            let unit = match words[n - 1] {
+                "m" | "min" | "mins" | "minute" | "minutes" => Some("minutes"),
+                "h" | "hour" | "hours" => Some("hours"),
+                "d" | "day" | "days" => Some("days"),
+                _ => None,
+            };
+            if let Some(unit) = unit {
+                let script = format!("set d to (current date) + {} * {}", amount, unit);
+                return (rest(3), Some((script, format!("in {} {}", amount, unit))));
+            }
+        }
+    }
+    let (at, skip) = if n >= 2 && words[n - 2] == "at" {
+        match parse_time(words[n - 1]) {
+            Some(t) => (Some(t), 2),
+            None => (None, 0),
+        }
+    } else {
+        (None, 0)
+    };
+    let tomorrow = n > skip && words[n - skip - 1] == "tomorrow";
+    if at.is_none() && !tomorrow {
+        return (text.to_string(), None);
+    }
+    let (hour, minute) = at.unwrap_or((9, 0));
+    let mut script = format!(
+        "set d to current date\nset time of d to {} * hours + {} * minutes",
+        hour, minute
+    );
+    let desc = if tomorrow {
+        script += "\nset d to d + 1 * days";
+        format!("tomorrow at {}:{:02}", hour, minute)
+    } else {
+        script += "\nif d < (current date) then set d to d + 1 * days";
+        format!("at {}:{:02}", hour, minute)
+    };
+    (rest(skip + tomorrow as usize), Some((script, desc)))
+}
+
+// `5pm`, `17:30`, `9:15am`
+fn parse_time(s: &str) -> Option<(u32, u32)> {
+    let lower = s.to_lowercase();
+    let (time, offset) = if let Some(t) = lower.strip_suffix("pm") {
+        (t, 12)
+    } else if let Some(t) = lower.strip_suffix("am") {
+        (t, 0)
+    } else {
+        (lower.as_str(), 0)
+    };
+    let (hour, minute) = time.split_once(':').unwrap_or((time, "0"));
+    let (hour, minute) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+    let hour = if offset > 0 || lower.ends_with("am") {
+        if hour == 0 || hour > 12 {
+            return None;
+        }
+        hour % 12 + offset
+    } else {
+        hour
+    };
+    (hour < 24 && minute < 60).then_some((hour, minute))
+}
+
+// A reminder or note, depending on `capture_app`
+fn capture(text: &str, config: &Config) -> Option<LauncherResult> {
+    let (text, when) = parse_when(text);
+    let text = text.as_str();
+    if text.is_empty() {
+        return None;
+    }
+    let list = &config.capture_list;
+    match config.capture_app.as_str() {
+        "Reminders" => {
+            let mut script = String::new();
+            let mut properties = format!("name:{}", applescript_string(text));
+            let mut desc = format!("Remind me: {}", text);
+            if let Some((date, when)) = when {
+                script += &date;
+                script += "\n";
+                properties += ", remind me date:d";
+                desc += &format!(" ({})", when);
+            }
+            let at = if list.is_empty() {
+                String::new()
+            } else {
+                format!(" at end of list {}", applescript_string(list))
+            };
+            script += &format!(
+                "tell application \"Reminders\" to make new reminder{} with properties {{{}}}",
+                at, properties
+            );
+            Some(LauncherResult::Script(desc, script))
+        }
+        "Notes" => {
+            let at = if list.is_empty() {
+                String::new()
+            } else {
+                format!(" at folder {}", applescript_string(list))
+            };
+            let script = format!(
+                "tell application \"Notes\" to make new note{} with properties {{body:{}}}",
+                at,
+                applescript_string(text)
+            );
+            Some(LauncherResult::Script(format!("Note: {}", text), script))
+        }
+        _ => None,
+    }
+}
+
+// Recipient and subject of `text` by `pattern`, e.g. `{to} about {subject}`. Without the
+// words between them, one word is the recipient and more are the subject
+pub fn parse_email(text: &str, pattern: &str) -> (String, String) {
+    let (to, subject) = match (pattern.find("{to}"), pattern.find("{subject}")) {
+        (Some(to), Some(subject)) => (to, subject),
+        _ => return (String::new(), text.to_string()),
+    };
+    let to_first = to < subject;
+    let between = if to_first {
+        &pattern[to + "{to}".len()..subject]
+    } else {
+        &pattern[subject + "{subject}".len()..to]
+    };
+    let between = format!(" {} ", between.trim().to_ascii_lowercase());
+    // also found at the start, for a missing part
+    let padded = format!(" {}", text);
+    let found = if between.trim().is_empty() {
+        text.split_once(' ')
+    } else {
+        // ignoring case, ASCII only so the indexes still fit `padded`
+        padded
+            .to_ascii_lowercase()
+            .find(&between)
+            .map(|i| (&padded[..i], &padded[i + between.len()..]))
+    };
+    match (found, to_first) {
+        (Some((first, second)), true) => (first.trim().to_string(), second.trim().to_string()),
+        (Some((first, second)), false) => (second.trim().to_string(), first.trim().to_string()),
+        (None, _) if !text.contains(' ') => (text.to_string(), String::new()),
+        (None, _) => (String::new(), text.to_string()),
+    }
+}
+
+// `mailto:` link opening a new message, empty parts are left out
+pub fn mailto(to: &str, subject: &str) -> String {
+    // everything but unreserved characters, so the link can't end a quoted shell word
+    let encode = |s: &str| {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (b as char).to_string()
+                }
+                b'@' => "@".to_string(),
+                _ => format!("%{:02X}", b),
+            })
+            .collect::<String>()
+    };
+    let mut link = format!("mailto:{}", encode(to));
+    if !subject.is_empty() {
+        link += &format!("?subject={}", encode(subject));
+    }
+    link
+}
+
+// Dark Mode, Finder's hidden files and desktop icons
+fn builtin_toggles() -> Vec<(String, Toggle)> {
+    let osascript = |script: &str| format!("osascript -e {}", shell_quote(script));
+    let appearance = "tell application \"System Events\" to tell appearance preferences to";
+    let finder = |key: &str| Toggle {
+        state: format!("defaults read com.apple.finder {}", key),
+        on: format!(
+            "defaults write com.apple.finder {} -bool true && killall Finder",
+            key
+        ),
+        off: format!(
+            "defaults write com.apple.finder {} -bool false && killall Finder",
+            key
+        ),
+    };
+    vec![
+        (
+            "Dark Mode".to_string(),
+            Toggle {
+                state: osascript(&format!("{} get dark mode", appearance)),
+                on: osascript(&format!("{} set dark mode to true", appearance)),
+                off: osascript(&format!("{} set dark mode to false", appearance)),
+            },
+        ),
+        ("Hidden Files".to_string(), finder("AppleShowAllFiles")),
+        ("Desktop Icons".to_string(), finder("CreateDesktop")),
+    ]
+}
+
+// The built-in toggles and those of `[toggles]`, which replace them by name
+fn toggles(query: &str, config: &Config) -> Vec<LauncherResult> {
+    let mut toggles = builtin_toggles();
+    toggles.retain(|(n, _)| !config.toggles.contains_key(n));
+    let mut user = config
+        .toggles
+        .clone()
+        .into_iter()
+        .collect::<Vec<(String, Toggle)>>();
+    user.sort_by(|a, b| a.0.cmp(&b.0));
+    toggles.extend(user);
+    match_toggles(query, &toggles, |t| toggle_state(&t.state))
+}
+
+// Results switching the toggles whose name starts with the query, after an optional `toggle`
+// and before an optional `on` or `off`. The current state shows if `state` knows it
+pub fn match_toggles(
+    query: &str,
+    toggles: &[(String, Toggle)],
+    state: impl Fn(&Toggle) -> Option<bool>,
+) -> Vec<LauncherResult> {
+    let query = query.to_lowercase();
+    let query = query.strip_prefix("toggle ").unwrap_or(&query).trim();
+    let (name, wanted) = match (query.strip_suffix(" on"), query.strip_suffix(" off")) {
+        (Some(name), _) => (name.trim(), Some(true)),
+        (_, Some(name)) => (name.trim(), Some(false)),
+        _ => (query, None),
+    };
+    // not for every word typed
+    if name.len() < 3 {
+        return vec![];
+    }
+    let word = |on: bool| if on { "on" } else { "off" };
+    let mut results = vec![];
+    for (title, toggle) in toggles {
+        if !title.to_lowercase().starts_with(name) {
+            continue;
+        }
+        let state = state(toggle);
+        let targets = match (wanted, state) {
+            (Some(on), _) => vec![on],
+            (None, Some(on)) => vec![!on],
+            (None, None) => vec![true, false],
+        };
+        for on in targets {
+            let cmd = if on { &toggle.on } else { &toggle.off };
+            if cmd.is_empty() {
+                continue;
+            }
+            let desc = match state {
+                Some(state) => format!("{}: {}, turn {}", title, word(state), word(on)),
+                None => format!("Turn {} {}", title, word(on)),
+            };
+            let script = format!("do shell script {}", applescript_string(cmd));
+            results.push(LauncherResult::Script(desc, script));
+        }
+    }
+    results
+}
+
+// What the `state` command of a toggle last printed, `None` until it has been read. It is read
+// again in the background once `TOGGLE_STATE_AGE` old, so typing never waits on it
+fn toggle_state(state: &str) -> Option<bool> {
+    if state.is_empty() {
+        return None;
+    }
+    let mut states = TOGGLE_STATES.lock().unwrap();
+    let known = states.get(state).copied();
+    if known.is_none_or(|(read, _)| read.elapsed() >= TOGGLE_STATE_AGE) {
+        // stamped now so it is read once at a time
+        states.insert(state.to_string(), (Instant::now(), known.and_then(|k| k.1)));
+        READING_TOGGLES.fetch_add(1, AtomicOrdering::Relaxed);
+        let state = state.to_string();
+        thread::spawn(move || {
+            let on = read_toggle_state(&state);
+            TOGGLE_STATES
+                .lock()
+                .unwrap()
+                .insert(state, (Instant::now(), on));
+            READING_TOGGLES.fetch_sub(1, AtomicOrdering::Relaxed);
+        });
+    }
+    known.and_then(|k| k.1)
+}
+
+// What the `state` command prints, `None` if it fails or prints something else
+pub fn parse_toggle_state(success: bool, stdout: &str) -> Option<bool> {
+    if !success {
+        return None;
+    }
+    match stdout.trim().to_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Some(true),
+        "0" | "false" | "off" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn read_toggle_state(state: &str) -> Option<bool> {
+    let output = Command::new("bash").arg("-c").arg(state).output().ok()?;
+    parse_toggle_state(
+        output.status.success(),
+        &String::from_utf8_lossy(&output.stdout),
+    )
+}
+
+pub fn run_applescript(script: &str) -> Result<(), Box<dyn Error>> {
+    applescript_output(script).map(|_| ())
+}
+
+// Opens the app at `path` and clicks the item of its app menu with the Cmd+, shortcut, which
+// is where apps keep their settings whatever the language. The app's process is found by its
+// bundle id once it is in front, for up to 5 seconds. Returns "none" without the item
+pub fn settings_script(path: &str) -> String {
+    format!(
+        r#"set bundle to id of application {}
+tell application id bundle to activate
+tell application "System Events"
+    if not UI elements enabled then error number -25211
+    repeat 50 times
+        try
+            if frontmost of (first process whose bundle identifier is bundle) then exit repeat
+        end try
+        delay 0.1
+    end repeat
+    tell (first process whose bundle identifier is bundle)
+        if not frontmost then error "The app did not come to the front"
+        repeat with i in menu items of menu 1 of menu bar item 2 of menu bar 1
+            try
+                if value of attribute "AXMenuItemCmdChar" of i is "," then
+                    click i
+                    return "opened"
+                end if
+            end try
+        end repeat
+    end tell
+end tell
+return "none""#,
+        applescript_string(path)
+    )
+}
+
+// What the script returns
+pub fn applescript_output(script: &str) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("osascript").arg("-e").arg(script).output()?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("(-1743)") {
+        Err("Not allowed to control the app. Allow it in System Settings > Privacy & Security > Automation".into())
+    } else if stderr.contains("(-1719)") || stderr.contains("(-25211)") {
+        Err("Not allowed to use Accessibility. Allow the terminal in System Settings > Privacy & Security > Accessibility".into())
+    } else {
+        Err(format!("AppleScript failed: {}", stderr.trim()).into())
+    }
+}
+
+// Process name of the terminal app the launcher runs in
+fn terminal_process() -> Option<&'static str> {
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let set = |var: &str| env::var_os(var).is_some();
+    match program.as_str() {
+        "Apple_Terminal" => Some("Terminal"),
+        "iTerm.app" => Some("iTerm2"),
+        "WezTerm" => Some("wezterm-gui"),
+        _ if set("KITTY_WINDOW_ID") => Some("kitty"),
+        _ if set("ALACRITTY_WINDOW_ID") || set("ALACRITTY_SOCKET") => Some("alacritty"),
+        _ => None,
+    }
+}
+
+// `on_accept`: get the terminal out of the way of the launched app, also `on_cancel`. Run
+// after the UI is gone, failures are only logged
+pub fn run_on_accept(hook: &str) {
+    if hook == "none" {
+        return;
+    }
+    let process = match terminal_process() {
+        Some(p) => applescript_string(p),
+        None => {
+            log("on_accept: unknown terminal, set TERM_PROGRAM");
+            return;
+        }
+    };
+    match hook {
+        "hide-terminal" => {
+            let script = format!(
+                "tell application \"System Events\" to set visible of process {} to false",
+                process
+            );
+            if let Err(e) = run_applescript(&script) {
+                log(&format!("on_accept: {}", e));
+            }
+        }
+        "close-window" => {
+            // once the launcher has exited, so the terminal has nothing to ask about
+            let script = format!(
+                "tell application \"System Events\" to tell process {} to click \
+                 (first button of front window whose subrole is \"AXCloseButton\")",
+                process
+            );
+            let cmd = format!(
+                "sleep 0.3; osascript -e {} 2>> {}",
+                shell_quote(&script),
+                shell_quote(&LOG_PATH)
+            );
+            let spawned = Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            if let Err(e) = spawned {
+                log(&format!("on_accept: {}", e));
+            }
+        }
+        _ => log(&format!("on_accept: unknown hook `{}`", hook)),
+    }
+}
+
+// Quote `s` as an AppleScript string literal
+pub fn applescript_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for ch in s.chars() {
+        if ch == '\\' || ch == '"' {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Processes listening on TCP `port`, from `lsof -i TCP:<port> -s TCP:LISTEN`, not those only
+// connected to it. One error result if there are none
+fn port_processes(port: &str, signal: &str) -> Vec<LauncherResult> {
+    let signal = signal.trim().to_uppercase();
+    let signal = signal.strip_prefix("SIG").unwrap_or(&signal).to_string();
+    let error = |e: String| vec![LauncherResult::Process(e, None, signal.clone())];
+    if port.is_empty() {
+        return error("Which port? e.g. `:port 3000`".into());
+    }
+    if port.parse::<u16>().is_err() {
+        return error(format!("`{}` is not a port", port));
+    }
+    let output = match Command::new("lsof")
+        .arg("-nP")
+        .arg("-i")
+        .arg(format!("TCP:{}", port))
+        .arg("-sTCP:LISTEN")
+        .arg("-F")
+        .arg("pcn")
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return error("`lsof` not found".into()),
+        Err(e) => return error(format!("lsof failed: {}", e)),
+    };
+    let processes = parse_lsof(&String::from_utf8_lossy(&output.stdout));
+    if processes.is_empty() {
+        // lsof only sees the processes of other users as root
+        return error(format!(
+            "No process of yours listens on port {}, `:sudo lsof -i :{}` lists all",
+            port, port
+        ));
+    }
+    processes
+        .into_iter()
+        .map(|(pid, command, name)| {
+            let desc = format!("{} (pid {}) {}", command, pid, name);
+            LauncherResult::Process(desc, Some(pid), signal.clone())
+        })
+        .collect()
+}
+
+// Pid, command and first connection of each process in the output of `lsof -F pcn`
+pub fn parse_lsof(output: &str) -> Vec<(u32, String, String)> {
+    let mut processes: Vec<(u32, String, String)> = vec![];
+    for line in output.lines() {
+        let (field, value) = match line.chars().next() {
+            Some(c) => line.split_at(c.len_utf8()),
+            None => continue,
+        };
+        match field {
+            "p" => {
+                if let Ok(pid) = value.parse() {
+                    processes.push((pid, String::new(), String::new()));
+                }
+            }
+            "c" => {
+                if let Some(p) = processes.last_mut() {
+                    p.1 = value.to_string();
+                }
+            }
+            "n" => {
+                if let Some(p) = processes.last_mut().filter(|p| p.2.is_empty()) {
+                    p.2 = value.to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+    processes
+}
+
+// Pastes `text` with Cmd+V into the app that was in front before the launcher's terminal,
+// then puts back what was on the clipboard. Only text is kept, other clipboard contents are
+// lost
+fn insert(text: &str) -> Result<(), Box<dyn Error>> {
+    let pid = menu::previous_app().ok_or("No app to insert into")?;
+    let saved = Command::new("pbpaste").output()?.stdout;
+    copy_to_clipboard(text)?;
+    // checked before switching apps, so a failure is seen in the launcher
+    let script = format!(
+        r#"tell application "System Events"
+    if not UI elements enabled then error number -25211
+    set frontmost of first process whose unix id is {} to true
+    delay 0.1
+    keystroke "v" using command down
+end tell"#,
+        pid
+    );
+    let pasted = applescript_output(&script);
+    // the app reads the clipboard some time after the keystroke
+    thread::sleep(Duration::from_millis(500));
+    if let Err(e) = copy_to_clipboard(&String::from_utf8_lossy(&saved)) {
+        log(&format!("failed to restore the clipboard: {}", e));
+    }
+    pasted.map(|_| ())
+}
+
+// Types `text` into the app that was in front before the launcher's terminal, leaving the
+// clipboard alone. Slower than `insert`, and the keys typed depend on the keyboard layout
+fn type_text(text: &str, delay_ms: u64) -> Result<(), Box<dyn Error>> {
+    let pid = menu::previous_app().ok_or("No app to insert into")?;
+    applescript_output(&typing_script(pid, text, delay_ms)).map(|_| ())
+}
+
+// System Events script that brings the app with `pid` to the front and types `text`,
+// `delay_ms` apart. Returns and tabs are pressed as keys
+pub fn typing_script(pid: u32, text: &str, delay_ms: u64) -> String {
+    let mut script = format!(
+        r#"tell application "System Events"
+    if not UI elements enabled then error number -25211
+    set frontmost of first process whose unix id is {} to true
+    delay 0.1
+"#,
+        pid
+    );
+    let key = |ch: char| match ch {
+        '\n' => "    key code 36\n".to_string(),
+        '\t' => "    key code 48\n".to_string(),
+        _ => format!("    keystroke {}\n", applescript_string(&ch.to_string())),
+    };
+    match delay_ms {
+        // lines at once, the keys in between
+        0 => {
+            let mut line = String::new();
+            for ch in text.chars() {
+                if ch != '\n' && ch != '\t' {
+                    line.push(ch);
+                    continue;
+                }
+                if !line.is_empty() {
+                    script += &format!("    keystroke {}\n", applescript_string(&line));
+                    line.clear();
+                }
+                script += &key(ch);
+            }
+            if !line.is_empty() {
+                script += &format!("    keystroke {}\n", applescript_string(&line));
+            }
+        }
+        _ => {
+            let delay = format!("    delay {}\n", delay_ms as f64 / 1000.0);
+            let keys = text.chars().map(key).collect::<Vec<String>>();
+            script += &keys.join(&delay);
+        }
+    }
+    script + "end tell"
+}
+
+// `kill -s <signal> <pid>`
+fn kill(pid: u32, signal: &str) -> Result<(), Box<dyn Error>> {
+    let output = Command::new("kill")
+        .arg("-s")
+        .arg(signal)
+        .arg(pid.to_string())
+        .output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("not permitted") {
+        Err(format!(
+            "Not allowed to kill pid {}, try `:sudo kill -s {} {}`",
+            pid, signal, pid
+        )
+        .into())
+    } else if stderr.contains("No such process") {
+        Err(format!("pid {} has already exited", pid).into())
+    } else {
+        Err(stderr.trim().to_string().into())
+    }
+}
+
+// The AppleScript running `cmd` as root, `cmd` reaching the shell as it is
+pub fn privileged_script(cmd: &str) -> String {
+    format!(
+        "do shell script {} with administrator privileges",
+        applescript_string(cmd)
+    )
+}
+
+// Let macOS ask for the password. The password never goes through the launcher
+pub fn run_privileged(cmd: &str) -> Result<(), Box<dyn Error>> {
+    // No shell in between, so the script needs no further escaping
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(privileged_script(cmd))
+        .output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("(-128)") {
+        Err("Authorization cancelled".into())
+    } else {
+        Err(format!("Privileged command failed: {}", stderr.trim()).into())
+    }
 }
 
 fn run_command(cmd: &str, param: &str) -> Result<bool, Box<dyn Error>> {
@@ -451,6 +3397,7 @@ fn run_command(cmd: &str, param: &str) -> Result<bool, Box<dyn Error>> {
 
             Ok(false)
         }
+        // bash expands variables itself, `load_env` added those missing
         "exec" => {
             spawn_process(param)?.wait()?;
             Ok(true)
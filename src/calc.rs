@@ -0,0 +1,277 @@
+// Arithmetic typed into the query, e.g. `2^10 / 3` or `0xff + 1`
+use crate::backend::{expand_path, log};
+use std::{collections::HashMap, fs, io::Write, sync::Mutex};
+
+lazy_static! {
+    // accepted expressions and their values, oldest first. `None` until loaded
+    static ref HISTORY: Mutex<Option<Vec<(String, f64)>>> = Mutex::new(None);
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    operators: usize, // a lone number is not a calculation
+    vars: &'a HashMap<String, f64>,
+    error: Option<String>, // the input is an expression, but has no value
+}
+
+impl Parser<'_> {
+    fn skip_spaces(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_spaces();
+        if self.chars.next_if_eq(&c).is_some() {
+            self.operators += 1;
+            return true;
+        }
+        false
+    }
+
+    // sum = product (('+' | '-') product)*. `a + b%` adds b percent of a
+    fn sum(&mut self) -> Option<f64> {
+        let mut value = self.product()?.0;
+        loop {
+            if self.eat('+') {
+                value += match self.product()? {
+                    (p, true) => value * p,
+                    (n, false) => n,
+                };
+            } else if self.eat('-') {
+                value -= match self.product()? {
+                    (p, true) => value * p,
+                    (n, false) => n,
+                };
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    // product = percent (('*' | '/' | '%') percent)*. Also whether it is a lone percentage
+    fn product(&mut self) -> Option<(f64, bool)> {
+        let (mut value, mut percent) = self.percent()?;
+        loop {
+            if self.eat('*') {
+                value *= self.percent()?.0;
+            } else if self.eat('/') {
+                value /= self.percent()?.0;
+            } else if self.eat('%') {
+                value %= self.percent()?.0;
+            } else {
+                return Some((value, percent));
+            }
+            percent = false;
+        }
+    }
+
+    // percent = unary '%'?, where the `%` is not followed by an operand
+    fn percent(&mut self) -> Option<(f64, bool)> {
+        let value = self.unary()?;
+        self.skip_spaces();
+        let mut ahead = self.chars.clone();
+        if ahead.next() == Some('%') {
+            while ahead.next_if(|c| c.is_whitespace()).is_some() {}
+            if ahead
+                .peek()
+                .is_none_or(|c| !(c.is_ascii_alphanumeric() || *c == '(' || *c == '.' || *c == '-'))
+            {
+                self.chars = ahead;
+                self.operators += 1;
+                return Some((value / 100.0, true));
+            }
+        }
+        Some((value, false))
+    }
+
+    // unary = '-' unary | power
+    fn unary(&mut self) -> Option<f64> {
+        self.skip_spaces();
+        if self.chars.next_if_eq(&'-').is_some() {
+            return Some(-self.unary()?);
+        }
+        self.power()
+    }
+
+    // power = atom ('^' unary)?, right associative
+    fn power(&mut self) -> Option<f64> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Some(base.powf(self.unary()?));
+        }
+        Some(base)
+    }
+
+    // atom = number | '(' sum ')' | name | name '(' sum ')'
+    fn atom(&mut self) -> Option<f64> {
+        self.skip_spaces();
+        if self.chars.next_if_eq(&'(').is_some() {
+            let value = self.sum()?;
+            self.skip_spaces();
+            self.chars.next_if_eq(&')')?;
+            return Some(value);
+        }
+        let mut word = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '_')
+        {
+            word.push(c);
+        }
+        if let Some(n) = number(&word) {
+            return Some(n);
+        }
+        match word.to_lowercase().as_str() {
+            "pi" => return Some(std::f64::consts::PI),
+            "e" => return Some(std::f64::consts::E),
+            // a calculation on its own, like a function
+            "ans" => {
+                self.operators += 1;
+                return Some(self.vars.get("ans").copied().unwrap_or_else(|| {
+                    self.error = Some("no previous result".to_string());
+                    0.0
+                }));
+            }
+            name => {
+                if let Some(value) = self.vars.get(name) {
+                    return Some(*value);
+                }
+            }
+        }
+        self.operators += 1;
+        let f: fn(f64) -> f64 = match word.to_lowercase().as_str() {
+            "sqrt" => f64::sqrt,
+            "abs" => f64::abs,
+            "ln" => f64::ln,
+            "log" => f64::log10,
+            "sin" => f64::sin,
+            "cos" => f64::cos,
+            "tan" => f64::tan,
+            "floor" => f64::floor,
+            "ceil" => f64::ceil,
+            "round" => f64::round,
+            _ => return None,
+        };
+        self.skip_spaces();
+        self.chars.next_if_eq(&'(')?;
+        let value = self.sum()?;
+        self.skip_spaces();
+        self.chars.next_if_eq(&')')?;
+        Some(f(value))
+    }
+}
+
+// Decimal, or hexadecimal, octal and binary with a `0x`, `0o` or `0b` prefix
+fn number(word: &str) -> Option<f64> {
+    let word = word.replace('_', "");
+    let radix = |digits: &str, radix| u64::from_str_radix(digits, radix).ok().map(|n| n as f64);
+    if let Some(d) = word.strip_prefix("0x") {
+        radix(d, 16)
+    } else if let Some(d) = word.strip_prefix("0o") {
+        radix(d, 8)
+    } else if let Some(d) = word.strip_prefix("0b") {
+        radix(d, 2)
+    } else if word.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        word.parse().ok()
+    } else {
+        None
+    }
+}
+
+// Value of the expression, `None` if it isn't one
+pub fn eval(expression: &str, vars: &HashMap<String, f64>) -> Option<Result<f64, String>> {
+    let mut parser = Parser {
+        chars: expression.chars().peekable(),
+        operators: 0,
+        vars,
+        error: None,
+    };
+    let value = parser.sum()?;
+    parser.skip_spaces();
+    if parser.chars.peek().is_some() || parser.operators == 0 {
+        return None;
+    }
+    if let Some(e) = parser.error {
+        return Some(Err(e));
+    }
+    if !value.is_finite() {
+        return Some(Err("not a number".to_string()));
+    }
+    Some(Ok(value))
+}
+
+// Loads the history from `file` the first time
+fn with_history<T>(file: Option<&str>, f: impl FnOnce(&mut Vec<(String, f64)>) -> T) -> T {
+    let mut history = HISTORY.lock().unwrap();
+    let history = history.get_or_insert_with(|| {
+        file.and_then(|f| fs::read_to_string(expand_path(f)).ok())
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| {
+                let (expression, value) = l.rsplit_once('\t')?;
+                Some((expression.to_string(), value.parse().ok()?))
+            })
+            .collect()
+    });
+    f(history)
+}
+
+// Accepted expressions and their values, newest first
+pub fn history(file: Option<&str>) -> Vec<(String, f64)> {
+    with_history(file, |h| h.iter().rev().cloned().collect())
+}
+
+// Variables for `eval`, `ans` is the last accepted value
+pub fn vars(file: Option<&str>) -> HashMap<String, f64> {
+    let mut vars = HashMap::new();
+    if let Some((_, value)) = with_history(file, |h| h.last().cloned()) {
+        vars.insert("ans".to_string(), value);
+    }
+    vars
+}
+
+// Adds the expression to the history, and with `save` to `file` if there is one. With
+// `dedup`, not if it is the last one again
+pub fn remember(expression: &str, value: f64, file: Option<&str>, dedup: bool, save: bool) {
+    let entry = (expression.to_string(), value);
+    if dedup && with_history(file, |h| h.last() == Some(&entry)) {
+        return;
+    }
+    with_history(file, |h| h.push(entry));
+    if let Some(path) = file.filter(|_| save).map(expand_path) {
+        let written = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| writeln!(f, "{}\t{}", expression, value));
+        if let Err(e) = written {
+            log(&format!(
+                "failed to save calculator history to `{}`: {}",
+                path, e
+            ));
+        }
+    }
+}
+
+// With at most `digits` significant digits, or as precise as f64 allows
+pub fn format(value: f64, digits: Option<usize>) -> String {
+    let digits = match digits {
+        Some(d) if value != 0.0 => d.max(1),
+        _ => return value.to_string(),
+    };
+    let magnitude = value.abs().log10().floor() as i32;
+    let trim = |s: &str| {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s.to_string()
+        }
+    };
+    if magnitude >= digits as i32 + 6 || magnitude < -6 {
+        let s = format!("{:.*e}", digits - 1, value);
+        let (mantissa, exponent) = s.split_once('e').unwrap_or((&s, "0"));
+        return format!("{}e{}", trim(mantissa), exponent);
+    }
+    let decimals = (digits as i32 - 1 - magnitude).max(0) as usize;
+    trim(&format!("{:.*}", decimals, value))
+}
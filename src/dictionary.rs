@@ -0,0 +1,118 @@
+// Definitions from the macOS dictionary, through DictionaryServices
+use std::{collections::HashMap, sync::Mutex};
+
+// Lookups kept, the cache starts over when full
+const MAX_CACHED: usize = 100;
+
+lazy_static! {
+    static ref DEFINITIONS: Mutex<HashMap<String, Option<String>>> = Mutex::new(HashMap::new());
+}
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::{c_char, c_void, CStr};
+
+    type CFStringRef = *const c_void;
+    type CFIndex = isize;
+
+    #[repr(C)]
+    struct CFRange {
+        location: CFIndex,
+        length: CFIndex,
+    }
+
+    const UTF8: u32 = 0x0800_0100; // kCFStringEncodingUTF8
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithBytes(
+            alloc: *const c_void,
+            bytes: *const u8,
+            num_bytes: CFIndex,
+            encoding: u32,
+            is_external_representation: u8,
+        ) -> CFStringRef;
+        fn CFStringGetLength(s: CFStringRef) -> CFIndex;
+        fn CFStringGetMaximumSizeForEncoding(length: CFIndex, encoding: u32) -> CFIndex;
+        fn CFStringGetCString(
+            s: CFStringRef,
+            buffer: *mut c_char,
+            size: CFIndex,
+            encoding: u32,
+        ) -> u8;
+        fn CFRelease(cf: *const c_void);
+    }
+
+    #[link(name = "CoreServices", kind = "framework")]
+    extern "C" {
+        fn DCSCopyTextDefinition(
+            dictionary: *const c_void,
+            text: CFStringRef,
+            range: CFRange,
+        ) -> CFStringRef;
+    }
+
+    pub fn define(word: &str) -> Option<String> {
+        // SAFETY: every CFString created or copied here is released exactly once, and the
+        // buffer is as large as CFStringGetCString may write
+        unsafe {
+            let text = CFStringCreateWithBytes(
+                std::ptr::null(),
+                word.as_ptr(),
+                word.len() as CFIndex,
+                UTF8,
+                0,
+            );
+            if text.is_null() {
+                return None;
+            }
+            // the range is in UTF-16 units, the whole word
+            let range = CFRange {
+                location: 0,
+                length: CFStringGetLength(text),
+            };
+            let definition = DCSCopyTextDefinition(std::ptr::null(), text, range);
+            CFRelease(text);
+            if definition.is_null() {
+                return None;
+            }
+            let size = CFStringGetMaximumSizeForEncoding(CFStringGetLength(definition), UTF8) + 1;
+            let mut buffer = vec![0 as c_char; size as usize];
+            let ok = CFStringGetCString(definition, buffer.as_mut_ptr(), size, UTF8);
+            CFRelease(definition);
+            if ok == 0 {
+                return None;
+            }
+            Some(
+                CStr::from_ptr(buffer.as_ptr())
+                    .to_string_lossy()
+                    .to_string(),
+            )
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod ffi {
+    pub fn define(_word: &str) -> Option<String> {
+        None
+    }
+}
+
+// Definition of `word`, `None` if the dictionaries don't have it
+pub fn define(word: &str) -> Option<String> {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() {
+        return None;
+    }
+    if let Some(definition) = DEFINITIONS.lock().unwrap().get(&word) {
+        return definition.clone();
+    }
+    let definition = ffi::define(&word);
+    let mut definitions = DEFINITIONS.lock().unwrap();
+    if definitions.len() >= MAX_CACHED {
+        definitions.clear();
+    }
+    definitions.insert(word, definition.clone());
+    definition
+}
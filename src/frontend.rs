@@ -1,31 +1,224 @@
-use crate::backend::LauncherResult;
+use crate::backend::{
+    action_icons, close_in_preview, log, match_indices, quick_look, source_status, sources, Action,
+    Config, LauncherResult, MatchKind, Matcher, ResultAction, ResultKind, SourceStatus,
+    Theme as ThemeConfig, CONFIG_PATH, HOME_PATH,
+};
+use crate::preview::{clear_images_escape, detect_graphics, image_escape, Details, Previews};
 use crossterm::{
     cursor,
     event::{poll, read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
-    execute,
+    execute, queue,
+    style::Print,
     terminal::{
         disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
         LeaveAlternateScreen,
     },
 };
+use std::time::{Duration, Instant};
 use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
     error::Error,
-    io::{self, Stdout},
+    hash::{Hash, Hasher},
+    io::{self, Stdout, Write},
+    path::{Path, PathBuf},
+    process::Child,
+    sync::Arc,
 };
-use std::{io::Write, time::Duration};
 use tui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    backend::{Backend, CrosstermBackend},
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Text},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    text::{Span, Spans, Text},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs, Widget},
     Terminal,
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// What the main loop should do after an input
+pub enum Input {
+    Continue,
+    Select(usize),          // Enter
+    Accept(usize),          // Enter on a result with an `accept_action`, the launcher stays open
+    Primary(usize, Action), // Enter with an action of `Config::enter`, run instead of selecting
+    SelectCopy(usize),      // Alt+Enter, also copy the result
+    OpenWith(usize),        // Ctrl+Enter / Ctrl+O, pick a file to open the selected app with
+    Action(Action),         // one of the selected result's actions
+    Filter,                 // Ctrl+F, toggle narrowing down the current results
+    Mode(usize),            // Ctrl+1..9, switch to a mode of `Config::modes`
+    NextMode,               // Ctrl+Tab
+    RepeatLast,             // Ctrl+R, repeat the last selection or action, also of a past session
+    ExpandGroup(usize),     // Ctrl+E, show all results of the selected one's group
+    Back,                   // Esc, leave the current stage
+    Exit,
+}
+
+// Of the panel toggled by the `histogram` key
+const HISTOGRAM_WIDTH: u16 = 24;
+
+// How long `App::flash` messages stay
+const FLASH: Duration = Duration::from_millis(1500);
+
+// Width a name with `detail_column` is cut down to before its detail is
+const MIN_NAME_WIDTH: usize = 16;
+
+// Default bindings, overridden by the `[keys]` table of the config.
+// Alternatives are separated by spaces
+const DEFAULT_KEYS: &[(&str, &str)] = &[
+    ("quick_look", "ctrl+y"),
+    ("open_with", "ctrl+enter ctrl+o"),
+    ("accept_and_copy", "alt+enter"),
+    ("copy_path", "ctrl+shift+c alt+c"),
+    ("copy_filename", "ctrl+shift+n alt+n"),
+    ("copy_escaped_path", "ctrl+shift+e alt+e"),
+    ("trash", "ctrl+t ctrl+delete"),
+    ("ignore", "ctrl+shift+h"),
+    ("open_in_space", "ctrl+shift+s"),
+    ("open_settings", "alt+,"),
+    ("kill", "ctrl+shift+k"),
+    ("insert", "ctrl+shift+v"),
+    ("rename", "f2"),
+    ("move", "ctrl+shift+m"),
+    ("details", "ctrl+d"),
+    ("repeat_last", "ctrl+r"),
+    ("copy_line", "alt+enter"),
+    ("filter", "ctrl+f"),
+    ("build_query", "ctrl+b"),
+    ("launch", "ctrl+l"),
+    ("next_mode", "ctrl+tab"),
+    ("histogram", "ctrl+g"),
+    ("back", "alt+up"),
+    ("expand_group", "ctrl+e"),
+    ("yank", "alt+y"),
+    ("paste", "alt+p"),
+    ("quit", "ctrl+c"),
+    ("actions", "ctrl+k"),
+];
+
+// Parse keys like `ctrl+shift+c` or `alt+enter`
+fn parse_key(spec: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts = spec.split('+').collect::<Vec<&str>>();
+    let key = parts.pop()?.to_lowercase();
+    for part in parts {
+        modifiers |= match part.to_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    let code = match key.as_str() {
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "esc" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next()?),
+        _ => KeyCode::F(key.strip_prefix('f')?.parse().ok()?),
+    };
+    Some((code, modifiers))
+}
+
+// In the syntax of `parse_key`, to paste into `[keys]`
+fn format_key(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let (code, modifiers) = normalize_key(code, modifiers);
+    let mut key = String::new();
+    for (modifier, name) in [
+        (KeyModifiers::CONTROL, "ctrl+"),
+        (KeyModifiers::ALT, "alt+"),
+        (KeyModifiers::SHIFT, "shift+"),
+    ] {
+        if modifiers.contains(modifier) {
+            key.push_str(name);
+        }
+    }
+    match code {
+        KeyCode::Char(' ') => key.push_str("space"),
+        KeyCode::Char(ch) => key.push(ch),
+        KeyCode::F(n) => key.push_str(&format!("f{}", n)),
+        code => key.push_str(&format!("{:?}", code).to_lowercase()),
+    }
+    key
+}
+
+// Shifted letters arrive either as upper case or with SHIFT
+fn normalize_key(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    match code {
+        KeyCode::Char(ch) if ch.is_uppercase() => (
+            KeyCode::Char(ch.to_lowercase().next().unwrap_or(ch)),
+            modifiers | KeyModifiers::SHIFT,
+        ),
+        _ => (code, modifiers),
+    }
+}
+
+struct Keymap(HashMap<String, Vec<(KeyCode, KeyModifiers)>>);
+
+impl Keymap {
+    fn new(keys: &HashMap<String, String>) -> Keymap {
+        let mut keymap = HashMap::new();
+        for (action, default) in DEFAULT_KEYS {
+            let spec = keys.get(*action).map(String::as_str).unwrap_or(default);
+            let bindings = spec
+                .split_whitespace()
+                .filter_map(|key| {
+                    let binding = parse_key(key);
+                    if binding.is_none() {
+                        log(&format!("invalid key `{}` for `{}`", key, action));
+                    }
+                    binding
+                })
+                .collect();
+            keymap.insert(action.to_string(), bindings);
+        }
+        Keymap(keymap)
+    }
+
+    fn is(&self, action: &str, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        let key = normalize_key(code, modifiers);
+        self.0
+            .get(action)
+            .is_some_and(|b| b.iter().any(|&(c, m)| normalize_key(c, m) == key))
+    }
+}
+
+// An input waiting for `y` or Enter
+struct Confirm {
+    message: String,
+    result: String, // `get_string` of the selected result
+    input: Input,
+}
+
+// An action's argument being typed in the input field, see `Action::argument`
+struct Argument {
+    prompt: &'static str,
+    text: String,
+    cursor_index: usize,
+    result: String, // `get_string` of the selected result
+    action: Action,
+}
+
+// The selected result's actions over the results, opened by the `actions` key. What Enter does
+// comes first
+struct ActionsMenu {
+    result: String,      // `get_string` of the selected result
+    enter: &'static str, // `enter_label` of the result
+    actions: Vec<ResultAction>,
+    selected: usize, // 0 for Enter's
+}
 
 // TODO: use stateful list
-pub struct App {
+pub struct App<B: Backend + Write = CrosstermBackend<Stdout>> {
     running: bool,
-    terminal: Terminal<CrosstermBackend<Stdout>>,
+    terminal: Terminal<B>,
     query: String,
     prompt: String,
     cursor_index: usize,
@@ -33,10 +226,71 @@ pub struct App {
     list_state: ListState,
     completion: bool,
     completion_content: Option<String>,
+    error: Option<String>,
+    confirming: Option<Confirm>,
+    argument: Option<Argument>,
+    selected: Option<LauncherResult>,
+    dir_trailing_slash: bool,
+    commit_trailing_space: bool,
+    keymap: Keymap,
+    flash: Option<(String, Instant)>,
+    previewer: String,
+    preview: Option<(String, Child)>, // path being quick looked
+    browsing: bool,                   // selection moved since the last edit
+    offset: usize,                    // index of the first visible result
+    more_indicator: bool,
+    max_rendered: usize,      // `Config::max_rendered`
+    matcher: Option<Matcher>, // to highlight what the query matched, `None` if not
+    pane: Option<Previews>,
+    drawn_image: Option<(PathBuf, Rect)>,
+    highlight_symbol_right: String,
+    action_icons: bool,
+    expand_selected_subtitle: bool,
+    empty_query: String,
+    filtering: bool,             // the query filters a fixed list
+    mode_prompt: Option<String>, // replaces the prompt while a keyword is typed
+    delete_forward: bool,
+    mode: Option<String>,               // shown in the input field's border
+    tabs: Option<(Vec<String>, usize)>, // names of the modes and the active one, see `Config::tabs`
+    breadcrumb: Option<Vec<String>>,    // see `set_breadcrumb`
+    actions_menu: Option<ActionsMenu>,
+    footer: Option<String>, // with `Config::show_footer`
+    seeded: bool,           // the query came from the clipboard and is replaced by typing
+    seed_hint: Option<String>,
+    frame_interval: Duration, // from `max_fps`
+    last_draw: Instant,
+    drawn_frame: Option<u64>, // hash of what is on screen
+    pending_frame: bool,      // a change waits for the next frame
+    plain: bool,              // no styling and ASCII only, for screen readers and dumb terminals
+    debug_keys: bool,         // flash keys that do nothing
+    top_hit: bool,            // the first result stands out, see `Cache::top_hits`
+    details: Details,
+    expanded: Option<String>, // path of the selected file whose details are shown
+    cursor_wrap: bool,        // Left at the start goes to the end, Right at the end to the start
+    enter: HashMap<String, String>, // `Config::enter`
+    spaces: HashMap<String, u32>, // `Config::spaces`
+    columns: bool,            // `Config::columns`
+    detail_column: bool,      // `Config::detail_column`
+    tab_enter_completes: bool, // Enter after Tab puts the completion into the query
+    building: bool,           // Enter adds the result to the query, see `add_to_query`
+    typing_commits: bool,     // a character typed after Tab goes after the completion
+    quit_key: String,         // `Config::quit_key`
+    quit_pressed: Option<Instant>, // the first press, with `quit_key = "double"`
+    grid: (usize, usize),     // columns drawn, and the index of the first result in them
+    last_key: Instant,
+    theme: Theme,
+    exit_clear: bool,
+    exit_print: bool,
+    pipe_closed: bool, // printing the launched result found nothing reading it
+    sources: Vec<&'static str>, // shown with their status, see `Config::show_provider_status`
+    histogram: bool,   // debug view of the results per source
+    groups: Option<Vec<(ResultKind, usize, usize)>>, // shown and total per kind, if grouped
+    registers: HashMap<char, String>, // `a` to `z`, for the session
+    register_key: Option<&'static str>, // "yank" or "paste", waiting for the register
 }
 
 impl App {
-    pub fn init(prompt: &str) -> Result<App, io::Error> {
+    pub fn init(prompt: &str, config: &Config) -> Result<App, io::Error> {
         std::panic::set_hook(Box::new(move |x| {
             cleanup_terminal();
             print!("{:?}", x);
@@ -45,7 +299,13 @@ impl App {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen)?;
-        let backend = CrosstermBackend::new(stdout);
+        App::with_backend(CrosstermBackend::new(stdout), prompt, config)
+    }
+}
+
+impl<B: Backend + Write> App<B> {
+    // Draws on `backend` as it is, without raw mode or the alternate screen, e.g. for tests
+    pub fn with_backend(backend: B, prompt: &str, config: &Config) -> Result<App<B>, io::Error> {
         let terminal = Terminal::new(backend)?;
         Ok(App {
             running: true,
@@ -57,183 +317,1602 @@ impl App {
             list_state: ListState::default(),
             completion: false,
             completion_content: None,
+            error: None,
+            confirming: None,
+            argument: None,
+            selected: None,
+            dir_trailing_slash: config.dir_trailing_slash,
+            commit_trailing_space: config.commit_trailing_space,
+            keymap: Keymap::new(&config.keys),
+            flash: None,
+            previewer: config.previewer.clone(),
+            preview: None,
+            browsing: false,
+            offset: 0,
+            more_indicator: config.more_indicator,
+            max_rendered: config.max_rendered,
+            matcher: config.highlight_matches.then(|| config.matcher.clone()),
+            pane: config
+                .preview_pane
+                .then(|| Previews::new(detect_graphics().filter(|_| !config.plain), config)),
+            drawn_image: None,
+            highlight_symbol_right: config.highlight_symbol_right.clone(),
+            action_icons: config.action_icons,
+            expand_selected_subtitle: config.expand_selected_subtitle,
+            empty_query: config.empty_query.clone(),
+            filtering: false,
+            mode_prompt: None,
+            delete_forward: config.delete_forward,
+            mode: None,
+            tabs: None,
+            breadcrumb: None,
+            actions_menu: None,
+            footer: config.show_footer.then(|| footer(config)),
+            seeded: false,
+            seed_hint: None,
+            frame_interval: match config.max_fps {
+                0 => Duration::ZERO,
+                fps => Duration::from_secs(1) / fps,
+            },
+            last_draw: Instant::now(),
+            drawn_frame: None,
+            pending_frame: false,
+            plain: config.plain,
+            debug_keys: config.debug_keys,
+            top_hit: false,
+            details: Details::new(),
+            expanded: None,
+            cursor_wrap: config.cursor_wrap,
+            enter: config.enter.clone(),
+            spaces: config.spaces.clone(),
+            columns: config.columns,
+            detail_column: config.detail_column,
+            tab_enter_completes: match config.tab_enter.as_str() {
+                "complete" => true,
+                "launch" => false,
+                other => {
+                    log(&format!("unknown tab_enter `{}`, launching instead", other));
+                    false
+                }
+            },
+            building: config.build_query,
+            quit_key: match config.quit_key.as_str() {
+                "cancel" | "copy" | "double" => config.quit_key.clone(),
+                other => {
+                    log(&format!("unknown quit_key `{}`, quitting instead", other));
+                    "cancel".to_string()
+                }
+            },
+            quit_pressed: None,
+            typing_commits: match config.completion_typing.as_str() {
+                "commit" => true,
+                "cancel" => false,
+                other => {
+                    log(&format!(
+                        "unknown completion_typing `{}`, committing instead",
+                        other
+                    ));
+                    true
+                }
+            },
+            grid: (1, 0),
+            last_key: Instant::now(),
+            theme: Theme::new(&config.theme),
+            exit_clear: config.exit_clear,
+            exit_print: config.exit_print,
+            pipe_closed: false,
+            sources: match config.show_provider_status {
+                true => sources(config),
+                false => vec![],
+            },
+            histogram: false,
+            groups: None,
+            registers: HashMap::new(),
+            register_key: None,
         })
     }
 
-    pub fn update<'a>(&'a mut self, list: &'a [LauncherResult]) -> Result<&'a mut App, io::Error> {
-        let list = if self.query.is_empty() { &[] } else { list };
+    pub fn update<'a>(&'a mut self, list: &'a [LauncherResult]) -> Result<&'a mut Self, io::Error> {
+        let hint = self.query.is_empty() && self.empty_query == "none" && !self.filtering;
+        let top_hit = self.top_hit && !hint;
+        let list = if hint { &[] } else { list };
         self.list_len = list.len();
         self.fix_selection();
-        let mut completion_content = None;
+        self.selected = self.list_state.selected().map(|i| list[i].clone());
+        if self.preview.as_ref().map(|(p, _)| p.as_str())
+            != self.selected.as_ref().and_then(|r| r.path())
+        {
+            self.close_preview();
+        }
+        if self.expanded.as_deref() != self.selected.as_ref().and_then(|r| r.path()) {
+            self.expanded = None;
+        }
+        let details = self.expanded.as_ref().map(|path| self.details.get(path));
+        // never confirm for a result that moved under the selection
+        if let Some(c) = &self.confirming {
+            if self.selected.as_ref().map(|r| r.get_string()) != Some(c.result.clone()) {
+                self.confirming = None;
+            }
+        }
+        if let Some(a) = &self.argument {
+            if self.selected.as_ref().map(|r| r.get_string()) != Some(a.result.clone()) {
+                self.argument = None;
+            }
+        }
+        if let Some(m) = &self.actions_menu {
+            if self.selected.as_ref().map(|r| r.get_string()) != Some(m.result.clone()) {
+                self.actions_menu = None;
+            }
+        }
+        // labels of the actions menu, with the key of each
+        let menu = self.actions_menu.as_ref().map(|m| {
+            let enter = (m.enter.to_string(), "↵".to_string());
+            let rows = [enter]
+                .into_iter()
+                .chain(m.actions.iter().map(|a| {
+                    let key = self.keymap.0.get(a.name).and_then(|b| b.first());
+                    let key = key.map(|(c, m)| format_key(*c, *m));
+                    (a.label.clone(), key.unwrap_or_default())
+                }))
+                .collect::<Vec<(String, String)>>();
+            (rows, m.selected)
+        });
+        // the footer takes the last row, the breadcrumb the first above the input field
+        let size = self.terminal.size()?;
+        let (footer_area, size) = match &self.footer {
+            Some(_) if size.height > 4 => (
+                Some(Rect {
+                    y: size.bottom() - 1,
+                    height: 1,
+                    ..size
+                }),
+                Rect {
+                    height: size.height - 1,
+                    ..size
+                },
+            ),
+            _ => (None, size),
+        };
+        let (breadcrumb_area, size) = match &self.breadcrumb {
+            Some(_) if size.height > 4 => (
+                Some(Rect { height: 1, ..size }),
+                Rect {
+                    y: size.y + 1,
+                    height: size.height - 1,
+                    ..size
+                },
+            ),
+            _ => (None, size),
+        };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+            .split(size);
+        // the tab bar takes the first row below the input field
+        let (tabs_area, results_area) = match &self.tabs {
+            Some(_) if chunks[1].height > 1 => (
+                Some(Rect {
+                    height: 1,
+                    ..chunks[1]
+                }),
+                Rect {
+                    y: chunks[1].y + 1,
+                    height: chunks[1].height - 1,
+                    ..chunks[1]
+                },
+            ),
+            _ => (None, chunks[1]),
+        };
+        // preview pane on the right of the results, if wide enough
+        let (list_area, pane_area) = match &self.pane {
+            Some(_) if results_area.width >= 60 => {
+                let split = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                    .split(results_area);
+                (split[0], Some(split[1]))
+            }
+            _ => (results_area, None),
+        };
+        // results per kind of source, beside the results
+        let (list_area, histogram_area) = match self.histogram {
+            true if list_area.width >= HISTOGRAM_WIDTH * 2 => (
+                Rect {
+                    width: list_area.width - HISTOGRAM_WIDTH,
+                    ..list_area
+                },
+                Some(Rect {
+                    x: list_area.right() - HISTOGRAM_WIDTH,
+                    width: HISTOGRAM_WIDTH,
+                    ..list_area
+                }),
+            ),
+            _ => (list_area, None),
+        };
+        let preview = match (&self.pane, pane_area, &self.selected) {
+            (Some(pane), Some(area), Some(LauncherResult::File(path))) => {
+                Some(pane.get(path, area.width.saturating_sub(2)))
+            }
+            (Some(pane), Some(_), Some(r)) => r
+                .snippet()
+                .map(|(code, language)| pane.snippet(code, language)),
+            _ => None,
+        };
+        let image = match (&preview, pane_area) {
+            (Some(p), Some(area)) if area.width > 2 && area.height > 2 => {
+                p.thumbnail.clone().map(|png| {
+                    let inner = Rect::new(area.x + 1, area.y + 1, area.width - 2, area.height - 2);
+                    (png, inner)
+                })
+            }
+            _ => None,
+        };
+        let completion_content = match (self.completion, self.list_state.selected()) {
+            (true, Some(i)) => {
+                let mut content = list[i].get_content();
+                if self.dir_trailing_slash
+                    && !content.ends_with('/')
+                    && Path::new(&content).is_dir()
+                {
+                    content.push('/');
+                }
+                // so the next term can be typed right away
+                if self.commit_trailing_space && list[i].path().is_none() && !content.ends_with(' ')
+                {
+                    content.push(' ');
+                }
+                Some(content)
+            }
+            _ => None,
+        };
+        self.completion_content = completion_content.clone();
+        // results that can be drawn, the window scrolls at most a screen from the selection.
+        // The others are not looked at, so huge lists cost no more than short ones. A screen
+        // holds a result per row, or with `columns` per cell of at least 4 columns
+        let screen = self.terminal.size()?;
+        let per_row = match self.columns {
+            true => (screen.width as usize / 4).max(1),
+            false => 1,
+        };
+        let max_rendered = self.max_rendered.max(2 * screen.height as usize * per_row);
+        let anchor = self.list_state.selected().unwrap_or(self.offset);
+        let near_end = (anchor.saturating_sub(max_rendered / 2) + max_rendered).min(list.len());
+        let near = near_end.saturating_sub(max_rendered)..near_end;
+        let statuses = self
+            .sources
+            .iter()
+            .map(|s| (*s, source_status(s)))
+            .collect::<Vec<(&str, SourceStatus)>>();
+
+        // draw only when something changed, and at most `max_fps` times a second
+        let mut hasher = DefaultHasher::new();
+        (
+            &self.query,
+            self.cursor_index,
+            self.shown_prompt(),
+            (self.seeded, self.building),
+            &completion_content,
+            (
+                (list.len(), &list[near.clone()]),
+                self.list_state.selected(),
+                self.filtering,
+                top_hit,
+                &self.groups,
+            ),
+            &self.error,
+            self.flash
+                .as_ref()
+                .filter(|(_, t)| t.elapsed() < FLASH)
+                .map(|(m, _)| m),
+            (
+                self.confirming.as_ref().map(|c| &c.message),
+                self.argument.as_ref().map(|a| (&a.text, a.cursor_index)),
+                &menu,
+            ),
+            &self.mode,
+            (
+                chunks[0],
+                chunks[1],
+                histogram_area,
+                (&self.tabs, &self.breadcrumb),
+            ),
+            (
+                preview.as_ref().map(|p| Arc::as_ptr(p) as usize),
+                &details,
+                &statuses,
+            ),
+        )
+            .hash(&mut hasher);
+        let frame = hasher.finish();
+        if self.drawn_frame == Some(frame) {
+            self.pending_frame = false;
+            return Ok(self);
+        }
+        if self.last_draw.elapsed() < self.frame_interval {
+            self.pending_frame = true;
+            return Ok(self);
+        }
+        self.drawn_frame = Some(frame);
+        self.last_draw = Instant::now();
+        self.pending_frame = false;
+
+        if self.drawn_image != image {
+            self.clear_image()?;
+        }
+        let mut cursor = (1, 1);
+        let prompt = self.shown_prompt().to_string();
         self.terminal.draw(|f| {
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
-                .split(f.size());
             // input field
             let block = Block::default().borders(Borders::ALL);
-            completion_content = if self.completion {
-                Some(
-                    list[self.list_state.selected().unwrap()]
-                        .get_string()
-                        .split_once('|')
-                        .unwrap()
-                        .1
-                        .trim()
-                        .to_string(),
-                )
+            let block = if let Some(e) = &self.error {
+                block.title(Span::styled(
+                    e.as_str(),
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ))
+            } else if let Some((m, _)) = self.flash.as_ref().filter(|(_, t)| t.elapsed() < FLASH) {
+                block.title(Span::styled(m.as_str(), Style::default().fg(Color::Green)))
+            } else if let Some(c) = &self.confirming {
+                block.title(Span::styled(
+                    c.message.as_str(),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ))
+            } else if let Some(m) = &self.mode {
+                block.title(Span::styled(
+                    format!("[{}]", m),
+                    Style::default().fg(Color::Cyan),
+                ))
+            } else {
+                block
+            };
+            let (text, cursor_x) = match &self.argument {
+                Some(a) => (a.text.clone(), a.text[..a.cursor_index].width()),
+                None => (
+                    completion_content
+                        .clone()
+                        .unwrap_or_else(|| self.query.clone()),
+                    completion_content
+                        .as_ref()
+                        .map_or(self.query[..self.cursor_index].width(), |c| c.width()),
+                ),
+            };
+            // terminal columns, wide characters take two
+            let cursor_x = prompt.width() + cursor_x;
+            let mut input_width = prompt.width() + text.width();
+            let text = if self.seeded {
+                Span::styled(text, Style::default().add_modifier(Modifier::REVERSED))
             } else {
-                None
+                Span::from(text)
             };
-            let input_field = self.prompt.clone()
-                + &completion_content
-                    .clone()
-                    .unwrap_or_else(|| self.query.clone());
-            let len = input_field.len();
-            let input_field = Text::from(Span::from(input_field));
+            let mut input_field = vec![Span::from(prompt.clone()), text];
+            if let Some(hint) = self.seed_hint.as_ref().filter(|_| self.seeded) {
+                input_width += hint.width() + 1;
+                input_field.push(Span::styled(
+                    format!(" {}", hint),
+                    Style::default().add_modifier(Modifier::DIM),
+                ));
+            }
+            let input_field = Text::from(Spans::from(input_field));
             let paragraph = Paragraph::new(input_field).block(block);
             f.render_widget(paragraph, chunks[0]);
-            f.set_cursor(1 + len as u16, 1);
-
-            // search result
-            let items = list
-                .iter()
-                .map(|r| ListItem::new(Span::from(r.get_string())))
-                .collect::<Vec<ListItem>>();
-            let items = List::new(items)
-                .block(Block::default().borders(Borders::ALL))
-                .highlight_style(
-                    Style::default()
-                        .fg(Color::Black)
-                        .bg(Color::White)
-                        .add_modifier(Modifier::BOLD),
-                )
-                .highlight_symbol(">> ");
-            f.render_stateful_widget(items, chunks[1], &mut self.list_state);
+            // what Enter does, on the right if the input leaves room
+            if let (Some(r), None) = (&self.selected, &self.argument) {
+                let label = match self.building {
+                    true => "Add",
+                    false => r.enter_label(&self.enter),
+                };
+                let action = if self.plain {
+                    format!("enter: {}", label)
+                } else {
+                    format!("↵ {}", label)
+                };
+                let width = action.width() as u16;
+                let inner = chunks[0].width.saturating_sub(2);
+                if chunks[0].height > 2 && input_width as u16 + width + 2 <= inner {
+                    let area =
+                        Rect::new(chunks[0].x + 1 + inner - width, chunks[0].y + 1, width, 1);
+                    let action = Span::styled(action, Style::default().add_modifier(Modifier::DIM));
+                    f.render_widget(Paragraph::new(action), area);
+                }
+            }
+            // the selection again, in place of the bottom border
+            if let (Some(r), true) = (&self.selected, self.plain && chunks[0].height > 2) {
+                let area = Rect::new(
+                    chunks[0].x + 1,
+                    chunks[0].y + 2,
+                    chunks[0].width.saturating_sub(2),
+                    1,
+                );
+                f.render_widget(Paragraph::new(format!("* {}", r.get_string())), area);
+            }
+            let max_x = chunks[0].width.saturating_sub(2) as usize;
+            cursor = (
+                chunks[0].x + 1 + cursor_x.min(max_x) as u16,
+                chunks[0].y + 1,
+            );
+            f.set_cursor(cursor.0, cursor.1);
+
+            if let (Some(levels), Some(area)) = (&self.breadcrumb, breadcrumb_area) {
+                let last = levels.len() - 1;
+                let spans = levels
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, level)| {
+                        let separator = (i > 0).then(|| Span::from(" > "));
+                        let style = match i == last {
+                            true => Style::default().add_modifier(Modifier::BOLD),
+                            false => Style::default().add_modifier(Modifier::DIM),
+                        };
+                        separator
+                            .into_iter()
+                            .chain([Span::styled(level.clone(), style)])
+                    })
+                    .collect::<Vec<Span>>();
+                f.render_widget(Paragraph::new(Spans::from(spans)), area);
+            }
+
+            if let (Some(footer), Some(area)) = (&self.footer, footer_area) {
+                let footer =
+                    Span::styled(footer.clone(), Style::default().add_modifier(Modifier::DIM));
+                f.render_widget(Paragraph::new(footer), area);
+            }
+
+            if let (Some((names, active)), Some(area)) = (&self.tabs, tabs_area) {
+                let titles = names
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| match self.plain && i == *active {
+                        true => Spans::from(format!("[{}]", name)),
+                        false => Spans::from(name.clone()),
+                    })
+                    .collect();
+                let tabs = Tabs::new(titles)
+                    .select(*active)
+                    .highlight_style(self.theme.selection);
+                f.render_widget(tabs, area);
+            }
+
+            // preview, the image is drawn over the empty pane after tui is done
+            if let Some(area) = pane_area {
+                let block = Block::default().borders(Borders::ALL).title("Preview");
+                match (&preview, &image) {
+                    (_, Some(_)) => f.render_widget(block, area),
+                    (Some(p), None) => {
+                        let text = Text::from(p.lines.clone());
+                        f.render_widget(Paragraph::new(text).block(block), area)
+                    }
+                    (None, None) => {
+                        let text = self.selected.as_ref().map(|r| r.get_content());
+                        let text = Text::from(text.unwrap_or_default());
+                        f.render_widget(Paragraph::new(text).block(block), area)
+                    }
+                }
+            }
+
+            let symbol = if self.plain { "* " } else { ">> " };
+            // row width left after the borders and the symbol
+            let row_width = (list_area.width as usize).saturating_sub(2 + symbol.width());
+            // of the right column with `detail_column`, the widest detail near the selection.
+            // Names are cut first, down to `MIN_NAME_WIDTH`
+            let detail_width = match self.detail_column {
+                true => list[near.clone()]
+                    .iter()
+                    .filter_map(|r| Some(r.name_and_detail()?.1.width()))
+                    .max()
+                    .unwrap_or(0)
+                    .min(row_width.saturating_sub(MIN_NAME_WIDTH + 1)),
+                false => 0,
+            };
+            // the selected row is as high as its full text, if `expand_selected_subtitle`
+            let wrapped = |r: &LauncherResult, selected: bool| match selected {
+                true if self.expand_selected_subtitle => wrap(&r.get_string(), row_width),
+                _ => vec![],
+            };
+            // what the query matched in the content of a result's `text`, by char index
+            let matched = |text: &str| -> (Vec<usize>, Style) {
+                let (start, start_chars) = match text.find('|') {
+                    Some(i) => (i + 1, text[..=i].chars().count()),
+                    None => (0, 0),
+                };
+                let found = self
+                    .matcher
+                    .as_ref()
+                    .filter(|_| !hint)
+                    .and_then(|m| match_indices(&text[start..], &self.query, m));
+                match found {
+                    Some((kind, indices)) => (
+                        indices.into_iter().map(|i| i + start_chars).collect(),
+                        match kind {
+                            MatchKind::Exact => self.theme.match_exact,
+                            MatchKind::Fuzzy => self.theme.match_fuzzy,
+                        },
+                    ),
+                    None => (vec![], Style::default()),
+                }
+            };
+            let item = |r: &LauncherResult, selected: bool, wrap: bool| {
+                // the icons before the selection marker, the text is cut to make room for both
+                let mut marker = match self.action_icons {
+                    true => match action_icons(&r.actions(&self.spaces)) {
+                        icons if icons.is_empty() => icons,
+                        icons => format!(" {}", icons),
+                    },
+                    false => String::new(),
+                };
+                if selected {
+                    marker.push_str(&self.highlight_symbol_right);
+                }
+                // the name is cut rather than the detail, which lines up with the others
+                let split = r.name_and_detail().filter(|_| detail_width > 0);
+                let text = match (split, marker.is_empty()) {
+                    (Some((name, detail)), _) => {
+                        let detail = align_right(&detail, "", detail_width);
+                        let detail = detail.trim_end();
+                        let padding = " ".repeat(detail_width - detail.width());
+                        let right = format!(" {}{}{}", padding, detail, marker);
+                        align_right(&name, &right, row_width)
+                    }
+                    (None, true) => r.get_string(),
+                    (None, false) => align_right(&r.get_string(), &marker, row_width),
+                };
+                // wrapped rows are not highlighted
+                let mut lines = match wrapped(r, selected && wrap) {
+                    w if w.len() > 1 => w.into_iter().map(Spans::from).collect(),
+                    _ => {
+                        let (indices, style) = match r.name_and_detail() {
+                            Some(_) if detail_width > 0 => matched(&text),
+                            _ => matched(&r.get_string()),
+                        };
+                        vec![Spans::from(highlighted(
+                            &text,
+                            &indices,
+                            Style::default(),
+                            style,
+                        ))]
+                    }
+                };
+                // details below the row, part of the selected item
+                if let (Some(details), true) = (&details, selected) {
+                    lines.extend(details.iter().map(|l| {
+                        Spans::from(Span::styled(
+                            format!("  {}", l),
+                            Style::default().add_modifier(Modifier::DIM),
+                        ))
+                    }));
+                }
+                let item = ListItem::new(Text::from(lines));
+                if let LauncherResult::Privileged(..) = r {
+                    item.style(Style::default().fg(Color::Red))
+                } else {
+                    item
+                }
+            };
+            let styled = |items: Vec<ListItem<'static>>, block: Block<'static>| {
+                List::new(items)
+                    .block(block)
+                    .highlight_style(self.theme.selection)
+                    .highlight_symbol(symbol)
+            };
+
+            // the top hit in its own box, the border below it separates the rest
+            let (area, list, selection, start) = match list.split_first() {
+                Some((first, rest)) if top_hit && list_area.height >= 6 => {
+                    let top = Rect::new(list_area.x, list_area.y, list_area.width, 3);
+                    let selected = self.list_state.selected() == Some(0);
+                    let block = Block::default().borders(Borders::ALL).title("Top Hit");
+                    let mut state = ListState::default();
+                    state.select(selected.then_some(0));
+                    f.render_stateful_widget(
+                        styled(vec![item(first, selected, false)], block),
+                        top,
+                        &mut state,
+                    );
+                    if let (Some(style), true) = (self.theme.marker, selected) {
+                        let marker = Rect::new(top.x + 1, top.y + 1, symbol.width() as u16, 1);
+                        f.render_widget(Restyle(style), marker.intersection(top));
+                    }
+                    let area = Rect::new(
+                        list_area.x,
+                        list_area.y + 3,
+                        list_area.width,
+                        list_area.height - 3,
+                    );
+                    let selection = self.list_state.selected().and_then(|i| i.checked_sub(1));
+                    (area, rest, selection, 1)
+                }
+                _ => (list_area, list, self.list_state.selected(), 0),
+            };
+
+            // results in a grid, row by row, if `columns` and they are short enough
+            let columns = match (&details, self.columns) {
+                (None, true) => {
+                    let widest = list[near.start.saturating_sub(start)..near.end - start]
+                        .iter()
+                        .map(|r| r.get_string().width())
+                        .max();
+                    let cell = widest.unwrap_or(0) + symbol.width() + 2;
+                    (area.width.saturating_sub(2) as usize / cell).clamp(1, list.len().max(1))
+                }
+                _ => 1,
+            };
+            self.grid = (columns, start);
+            let hidden = if columns > 1 {
+                let cell = area.width.saturating_sub(2) as usize / columns;
+                let total = list.len().div_ceil(columns);
+                let height = area.height.saturating_sub(2) as usize;
+                let overflow = self.more_indicator && total > height;
+                let rows = if overflow {
+                    height.saturating_sub(1)
+                } else {
+                    height
+                };
+                let mut first = self.offset / columns;
+                if let Some(row) = selection.map(|i| i / columns) {
+                    if row >= first + rows {
+                        first = (row + 1).saturating_sub(rows);
+                    } else if row < first {
+                        first = row;
+                    }
+                }
+                first = first.min(total.saturating_sub(rows));
+                self.offset = first * columns;
+                let end = ((first + rows) * columns).min(list.len());
+                let lines = list[self.offset..end]
+                    .chunks(columns)
+                    .enumerate()
+                    .map(|(row, results)| {
+                        let spans = results.iter().enumerate().map(|(column, r)| {
+                            let selected = selection == Some(self.offset + row * columns + column);
+                            let style = match r {
+                                _ if selected => self.theme.selection,
+                                LauncherResult::Privileged(..) => Style::default().fg(Color::Red),
+                                _ => Style::default(),
+                            };
+                            let marker = if selected {
+                                let marker_style = self.theme.marker.unwrap_or(style);
+                                Span::styled(symbol, marker_style)
+                            } else {
+                                Span::from(" ".repeat(symbol.width()))
+                            };
+                            let width = cell.saturating_sub(symbol.width());
+                            let text = align_right(&r.get_string(), "", width);
+                            let (indices, matched_style) = matched(&r.get_string());
+                            let text = highlighted(&text, &indices, style, matched_style);
+                            [marker].into_iter().chain(text)
+                        });
+                        Spans::from(spans.flatten().collect::<Vec<Span>>())
+                    })
+                    .collect::<Vec<Spans>>();
+                let block = Block::default().borders(Borders::ALL);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+                (overflow && rows > 0).then(|| list.len() - end)
+            } else {
+                let height = area.height.saturating_sub(2) as usize;
+                let overflow = self.more_indicator && list.len() > height;
+                // keep the last row for `+N more`, and room for the details
+                let rows = if overflow {
+                    height.saturating_sub(1)
+                } else {
+                    height
+                };
+                let rows = rows.saturating_sub(details.as_ref().map_or(0, |d| d.len()));
+                let extra = selection
+                    .and_then(|i| list.get(i))
+                    .map_or(0, |r| wrapped(r, true).len().saturating_sub(1));
+                let rows = rows - extra.min(rows.saturating_sub(1));
+                if let Some(i) = selection {
+                    if i >= self.offset + rows {
+                        self.offset = (i + 1).saturating_sub(rows);
+                    } else if i < self.offset {
+                        self.offset = i;
+                    }
+                }
+                self.offset = self.offset.min(list.len().saturating_sub(rows));
+                // a group starts with its heading, also at the top when scrolled into it
+                let groups = self.groups.as_ref();
+                let heading = |first: usize, i: usize| {
+                    groups.is_some() && (i == first || list[i].kind() != list[i - 1].kind())
+                };
+                let lines = |first: usize, last: usize| {
+                    (first..=last)
+                        .map(|i| 1 + heading(first, i) as usize)
+                        .sum::<usize>()
+                };
+                if let Some(i) = selection.filter(|_| groups.is_some()) {
+                    while self.offset < i && lines(self.offset, i) > rows {
+                        self.offset += 1;
+                    }
+                }
+                let mut end = self.offset;
+                while end < list.len() && lines(self.offset, end) <= rows {
+                    end += 1;
+                }
+                let window = &list[self.offset..end];
+                let selected = selection.and_then(|i| i.checked_sub(self.offset));
+                let mut items = vec![];
+                let mut selected_row = None;
+                for (i, r) in window.iter().enumerate() {
+                    if let Some(groups) = groups.filter(|_| heading(self.offset, self.offset + i)) {
+                        items.push(group_heading(groups, r.kind()));
+                    }
+                    if Some(i) == selected {
+                        selected_row = Some(items.len());
+                    }
+                    items.push(item(r, Some(i) == selected, true));
+                }
+                let mut state = ListState::default();
+                state.select(selected_row);
+                f.render_stateful_widget(
+                    styled(items, Block::default().borders(Borders::ALL)),
+                    area,
+                    &mut state,
+                );
+                if let (Some(style), Some(row)) = (self.theme.marker, selected_row) {
+                    let y = area.y + 1 + row as u16;
+                    let marker = Rect::new(area.x + 1, y, symbol.width() as u16, 1);
+                    f.render_widget(Restyle(style), marker.intersection(area));
+                }
+                (overflow && rows > 0).then(|| list.len() - self.offset - window.len())
+            };
+            if hint && area.height > 2 && area.width > 2 {
+                let text = Paragraph::new(Span::styled(
+                    "Type to search apps, files and commands",
+                    Style::default().add_modifier(Modifier::DIM),
+                ));
+                f.render_widget(text, Rect::new(area.x + 1, area.y + 1, area.width - 2, 1));
+            }
+            if let Some(hidden) = hidden.filter(|h| *h > 0 && area.height > 2 && area.width > 2) {
+                let more = Paragraph::new(Span::styled(
+                    format!("+{} more", hidden),
+                    Style::default().add_modifier(Modifier::DIM),
+                ));
+                let line = Rect::new(area.x + 1, area.y + area.height - 2, area.width - 2, 1);
+                f.render_widget(more, line);
+            }
+            if let Some(area) = histogram_area {
+                let width = area.width.saturating_sub(2);
+                f.render_widget(histogram(list, width, self.plain), area);
+            }
+            // on the bottom border of the results, right aligned
+            if !statuses.is_empty() && list_area.height >= 2 {
+                let spans = statuses
+                    .iter()
+                    .flat_map(|(source, status)| {
+                        let (color, word) = match status {
+                            SourceStatus::Ready => (Color::Green, "ready".to_string()),
+                            SourceStatus::Searching => (Color::Yellow, "searching".to_string()),
+                            SourceStatus::Failed(e) => (Color::Red, e.clone()),
+                        };
+                        let (dot, name) = match (self.plain, status) {
+                            (true, _) => (String::new(), format!("{}: {}", source, word)),
+                            (false, SourceStatus::Failed(e)) => {
+                                ("●".to_string(), format!(" {}: {}", source, e))
+                            }
+                            (false, _) => ("●".to_string(), format!(" {}", source)),
+                        };
+                        [
+                            Span::from(" "),
+                            Span::styled(dot, Style::default().fg(color)),
+                            Span::styled(name, Style::default().add_modifier(Modifier::DIM)),
+                        ]
+                    })
+                    .collect::<Vec<Span>>();
+                let spans = Spans::from(spans);
+                let width = (spans.width() as u16 + 1).min(list_area.width.saturating_sub(2));
+                let line = Rect::new(
+                    list_area.right() - 1 - width,
+                    list_area.bottom() - 1,
+                    width,
+                    1,
+                );
+                f.render_widget(Paragraph::new(spans), line);
+            }
+            // the actions menu in the middle of the results, over them
+            if let Some((rows, selected)) = &menu {
+                let label_width = rows.iter().map(|(l, _)| l.width()).max().unwrap_or(0);
+                let key_width = rows.iter().map(|(_, k)| k.width()).max().unwrap_or(0);
+                let width = (label_width + key_width + symbol.width() + 4) as u16;
+                let width = width.min(list_area.width);
+                let height = (rows.len() as u16 + 2).min(list_area.height);
+                let area = Rect::new(
+                    list_area.x + (list_area.width - width) / 2,
+                    list_area.y + (list_area.height - height) / 2,
+                    width,
+                    height,
+                );
+                let items = rows
+                    .iter()
+                    .map(|(label, key)| {
+                        let padding = " ".repeat(label_width - label.width() + 1);
+                        ListItem::new(Spans::from(vec![
+                            Span::from(format!("{}{}", label, padding)),
+                            Span::styled(key.clone(), Style::default().add_modifier(Modifier::DIM)),
+                        ]))
+                    })
+                    .collect::<Vec<ListItem>>();
+                let block = Block::default().borders(Borders::ALL).title("Actions");
+                let mut state = ListState::default();
+                state.select(Some(*selected));
+                f.render_widget(tui::widgets::Clear, area);
+                f.render_stateful_widget(styled(items, block), area, &mut state);
+            }
+            if self.plain {
+                f.render_widget(Plain, f.size());
+            }
         })?;
-        self.completion_content = completion_content;
+        if let (Some((png, area)), None) = (&image, &self.drawn_image) {
+            let graphics = self.pane.as_ref().and_then(|p| p.graphics);
+            if let Some(escape) =
+                graphics.and_then(|g| image_escape(g, png, area.width, area.height))
+            {
+                let backend = self.terminal.backend_mut();
+                queue!(backend, cursor::MoveTo(area.x, area.y), Print(escape))?;
+                queue!(backend, cursor::MoveTo(cursor.0, cursor.1))?;
+                Write::flush(backend)?;
+                self.drawn_image = image;
+            }
+        }
         Ok(self)
     }
 
+    // Remove the preview image. tui thinks the cells below it are empty, so make them so
+    fn clear_image(&mut self) -> io::Result<()> {
+        let graphics = self.pane.as_ref().and_then(|p| p.graphics);
+        if let (Some((_, area)), Some(graphics)) = (self.drawn_image.take(), graphics) {
+            let backend = self.terminal.backend_mut();
+            queue!(backend, Print(clear_images_escape(graphics)))?;
+            for y in area.y..area.y + area.height {
+                queue!(
+                    backend,
+                    cursor::MoveTo(area.x, y),
+                    Print(" ".repeat(area.width as usize))
+                )?;
+            }
+            Write::flush(backend)?;
+        }
+        Ok(())
+    }
+
     fn replace_query(&mut self) {
-        if let Some(s) = &self.completion_content {
-            self.query = s.to_string();
+        // taken, keys before the next `update` must not replace the query again
+        if let Some(s) = self.completion_content.take() {
+            self.query = s;
             self.cursor_index = self.query.len();
             self.completion = false;
         }
     }
 
-    pub fn wait_input(&mut self, index: &mut Option<usize>) -> Result<bool, Box<dyn Error>> {
-        loop {
-            if !poll(Duration::from_millis(30))? {
-                return Ok(false);
+    pub fn wait_input(&mut self) -> Result<Input, Box<dyn Error>> {
+        // come back in time for a frame that was held back
+        let timeout = if self.pending_frame {
+            self.frame_interval.saturating_sub(self.last_draw.elapsed())
+        } else {
+            Duration::from_millis(30)
+        };
+        if !poll(timeout)? {
+            return Ok(Input::Continue);
+        }
+        Ok(self.handle_event(read()?))
+    }
+
+    // What a terminal event means, `Input::Continue` for those that only change the screen
+    pub fn handle_event(&mut self, event: Event) -> Input {
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            state: _,
+        }) = event
+        {
+            self.last_key = Instant::now();
+            self.error = None;
+            self.flash = None;
+            // the second press of a double quit comes right after the first
+            if !self.keymap.is("quit", code, modifiers) {
+                self.quit_pressed = None;
+            }
+            if let Some(c) = self.confirming.take() {
+                if let KeyCode::Enter | KeyCode::Char('y') | KeyCode::Char('Y') = code {
+                    return c.input;
+                }
+                return Input::Continue;
+            }
+            if self.argument.is_some() {
+                return self.argument_key(code, modifiers);
+            }
+            if self.actions_menu.is_some() {
+                return self.actions_menu_key(code, modifiers);
+            }
+            if self.keymap.is("actions", code, modifiers) {
+                match &self.selected {
+                    Some(r) => {
+                        self.actions_menu = Some(ActionsMenu {
+                            result: r.get_string(),
+                            enter: r.enter_label(&self.enter),
+                            actions: r.actions(&self.spaces),
+                            selected: 0,
+                        })
+                    }
+                    None => {
+                        self.flash("Nothing selected");
+                    }
+                }
+                return Input::Continue;
+            }
+            // the key after yank or paste names the register, anything else cancels
+            if let Some(op) = self.register_key.take() {
+                if let KeyCode::Char(register @ 'a'..='z') = code {
+                    self.use_register(op, register);
+                }
+                return Input::Continue;
             }
-            if let Event::Key(KeyEvent {
-                code,
-                modifiers,
-                kind: KeyEventKind::Press | KeyEventKind::Repeat,
-                state: _,
-            }) = read()?
+            if let Some(op) = ["yank", "paste"]
+                .into_iter()
+                .find(|op| self.keymap.is(op, code, modifiers))
             {
-                if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
-                    return Ok(true);
-                }
-                macro_rules! move_selection {
-                    ($list_len:expr, $state:expr, $i:expr, $dir:expr) => {
-                        if $list_len > 0 {
-                            $state.select(if let Some(i) = $state.selected() {
-                                let i = i as i64 + $dir;
-                                let i = if i < 0 {
-                                    $list_len - 1
-                                } else {
-                                    i as usize % $list_len
-                                };
-                                Some(i)
-                            } else {
-                                None
-                            })
-                        }
-                    };
+                self.register_key = Some(op);
+                self.flash(&format!("{} register: a-z", op));
+                return Input::Continue;
+            }
+            if let Some(a) = self.selected.as_ref().and_then(|r| {
+                r.actions(&self.spaces)
+                    .into_iter()
+                    .find(|a| self.keymap.is(a.name, code, modifiers))
+            }) {
+                return self.run_action(a);
+            }
+            if self.keymap.is("quit", code, modifiers) {
+                return self.quit();
+            }
+            // space previews like in Finder, unless typing
+            let space_preview = code == KeyCode::Char(' ')
+                && self.browsing
+                && self.cursor_index == self.query.len()
+                && matches!(self.selected, Some(LauncherResult::File(_)));
+            if space_preview || self.keymap.is("quick_look", code, modifiers) {
+                self.toggle_preview();
+                return Input::Continue;
+            }
+            if self.keymap.is("back", code, modifiers) {
+                return Input::Back;
+            }
+            if self.keymap.is("histogram", code, modifiers) {
+                self.histogram = !self.histogram;
+                return Input::Continue;
+            }
+            if self.keymap.is("expand_group", code, modifiers) && self.groups.is_some() {
+                if let Some(i) = self.list_state.selected() {
+                    return Input::ExpandGroup(i);
                 }
-                match code {
-                    KeyCode::Char(ch) => {
-                        self.replace_query();
-                        if self.cursor_index == self.query.len() {
-                            self.query.push(ch);
+            }
+            if self.keymap.is("details", code, modifiers) {
+                if let (None, Some(LauncherResult::File(path))) = (&self.expanded, &self.selected) {
+                    self.expanded = Some(path.clone());
+                } else {
+                    self.expanded = None;
+                }
+                return Input::Continue;
+            }
+            macro_rules! move_selection {
+                ($list_len:expr, $state:expr, $i:expr, $dir:expr) => {
+                    if $list_len > 0 {
+                        $state.select(if let Some(i) = $state.selected() {
+                            let i = i as i64 + $dir;
+                            let i = if i < 0 {
+                                $list_len - 1
+                            } else {
+                                i as usize % $list_len
+                            };
+                            Some(i)
                         } else {
-                            self.query.insert(self.cursor_index, ch);
-                        }
-                        self.cursor_index += 1;
-                        return Ok(false);
+                            None
+                        })
+                    }
+                };
+            }
+            if self.keymap.is("open_with", code, modifiers) {
+                return self
+                    .list_state
+                    .selected()
+                    .map_or(Input::Continue, Input::OpenWith);
+            }
+            if self.keymap.is("accept_and_copy", code, modifiers) {
+                return self
+                    .list_state
+                    .selected()
+                    .map_or(Input::Continue, Input::SelectCopy);
+            }
+            if self.keymap.is("filter", code, modifiers) {
+                return Input::Filter;
+            }
+            if self.keymap.is("build_query", code, modifiers) {
+                self.building = !self.building;
+                self.flash(match self.building {
+                    true => "Enter adds the result to the query",
+                    false => "Enter launches",
+                });
+                return Input::Continue;
+            }
+            // what Enter does outside of `building`
+            if self.keymap.is("launch", code, modifiers) {
+                return self.launch();
+            }
+            if self.keymap.is("repeat_last", code, modifiers) {
+                return Input::RepeatLast;
+            }
+            if self.keymap.is("next_mode", code, modifiers) {
+                return Input::NextMode;
+            }
+            if let (KeyCode::Char(ch @ '1'..='9'), true) =
+                (code, modifiers.contains(KeyModifiers::CONTROL))
+            {
+                return Input::Mode(ch as usize - '1' as usize);
+            }
+            // a query from the clipboard is replaced by typing, the arrows keep it
+            if self.seeded {
+                match code {
+                    KeyCode::Char(_) => {
+                        self.set_query("");
+                        self.seeded = false;
                     }
                     KeyCode::Backspace | KeyCode::Delete => {
+                        self.set_query("");
+                        self.seeded = false;
+                        return Input::Continue;
+                    }
+                    KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End => {
+                        self.seeded = false
+                    }
+                    _ => {}
+                }
+            }
+            match code {
+                // e.g. `ctrl+x`, which would otherwise type `x`
+                KeyCode::Char(_)
+                    if self.debug_keys
+                        && modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    self.flash(&format!("{} unbound", format_key(code, modifiers)));
+                    return Input::Continue;
+                }
+                KeyCode::Char(ch) => {
+                    self.browsing = false;
+                    // otherwise the character goes into the query as typed before Tab
+                    if self.typing_commits {
+                        self.replace_query();
+                    } else {
                         self.completion = false;
-                        if self.cursor_index > 0 {
-                            self.query = self.query[0..self.cursor_index - 1].to_string()
-                                + &self.query[self.cursor_index..];
-                            self.cursor_index -= 1;
-                        }
-                        return Ok(false);
+                        self.completion_content = None;
                     }
-                    KeyCode::Up => {
-                        move_selection!(self.list_len, self.list_state, i, -1);
-                        return Ok(false);
+                    self.query.insert(self.cursor_index, ch);
+                    self.cursor_index += ch.len_utf8();
+                    return Input::Continue;
+                }
+                KeyCode::Delete if self.delete_forward => {
+                    self.browsing = false;
+                    self.completion = false;
+                    // the cursor stays, nothing to delete at the end
+                    if self.cursor_index < self.query.len() {
+                        self.query.remove(self.cursor_index);
                     }
-                    KeyCode::Down => {
-                        move_selection!(self.list_len, self.list_state, i, 1);
-                        return Ok(false);
+                    return Input::Continue;
+                }
+                KeyCode::Backspace | KeyCode::Delete => {
+                    self.browsing = false;
+                    self.completion = false;
+                    if let Some(i) = self.prev_char_boundary() {
+                        self.query.remove(i);
+                        self.cursor_index = i;
                     }
-                    KeyCode::Left => {
-                        self.replace_query();
-                        if self.cursor_index > 0 {
-                            self.cursor_index -= 1;
-                        }
-                        return Ok(false);
+                    return Input::Continue;
+                }
+                KeyCode::Up | KeyCode::Down if self.grid.0 > 1 => {
+                    self.browsing = true;
+                    self.move_in_grid(code);
+                    return Input::Continue;
+                }
+                // Left and Right edit the query until the selection is moved
+                KeyCode::Left | KeyCode::Right if self.grid.0 > 1 && self.browsing => {
+                    self.move_in_grid(code);
+                    return Input::Continue;
+                }
+                KeyCode::Up => {
+                    self.browsing = true;
+                    move_selection!(self.list_len, self.list_state, i, -1);
+                    return Input::Continue;
+                }
+                KeyCode::Down => {
+                    self.browsing = true;
+                    move_selection!(self.list_len, self.list_state, i, 1);
+                    return Input::Continue;
+                }
+                KeyCode::Left => {
+                    // accepting a completion doesn't wrap
+                    let wrap = self.cursor_wrap && self.completion_content.is_none();
+                    self.replace_query();
+                    if let Some(i) = self.prev_char_boundary() {
+                        self.cursor_index = i;
+                    } else if wrap {
+                        self.cursor_index = self.query.len();
+                    }
+                    return Input::Continue;
+                }
+                KeyCode::Right => {
+                    // accepting a completion doesn't wrap
+                    let wrap = self.cursor_wrap && self.completion_content.is_none();
+                    self.replace_query();
+                    if let Some(ch) = self.query[self.cursor_index..].chars().next() {
+                        self.cursor_index += ch.len_utf8();
+                    } else if wrap {
+                        self.cursor_index = 0;
                     }
-                    KeyCode::Right => {
+                    return Input::Continue;
+                }
+                KeyCode::Enter => {
+                    // the completion becomes the query to keep typing, if so configured
+                    if self.tab_enter_completes && self.completion_content.is_some() {
+                        self.browsing = false;
                         self.replace_query();
-                        if self.cursor_index < self.query.len() {
-                            self.cursor_index += 1;
-                        }
-                        return Ok(false);
+                        return Input::Continue;
                     }
-                    KeyCode::Enter => {
-                        *index = self.list_state.selected();
-                        return Ok(!index.is_none());
+                    if self.building {
+                        self.add_to_query();
+                        return Input::Continue;
                     }
-                    KeyCode::Tab => {
-                        self.completion = self.list_len > 0;
-                        move_selection!(self.list_len, self.list_state, i, 1);
-                        return Ok(false);
+                    return self.launch();
+                }
+                KeyCode::Tab => {
+                    self.completion = self.list_len > 0;
+                    move_selection!(self.list_len, self.list_state, i, 1);
+                    return Input::Continue;
+                }
+                KeyCode::Esc => {
+                    // cancel completion, or go back to the previous stage. At the first one, with
+                    // no breadcrumb, quit
+                    if !self.completion {
+                        return match self.breadcrumb {
+                            Some(_) => Input::Back,
+                            None => Input::Exit,
+                        };
                     }
-                    KeyCode::Esc => {
-                        // cancel completion
-                        self.completion = false;
+                    self.completion = false;
+                }
+                _ => {
+                    if self.debug_keys {
+                        self.flash(&format!("{} unbound", format_key(code, modifiers)));
                     }
-                    _ => return Ok(false),
+                    return Input::Continue;
+                }
+            }
+        }
+        Input::Continue
+    }
+
+    // Yank the selected result, or the query without one, or paste at the cursor like typing
+    fn use_register(&mut self, op: &str, register: char) {
+        if op == "yank" {
+            let text = match &self.selected {
+                Some(r) => r.get_content(),
+                None => self.query.clone(),
+            };
+            self.flash(&format!("Yanked to \"{}", register));
+            self.registers.insert(register, text);
+            return;
+        }
+        let text = match self.registers.get(&register) {
+            Some(text) => text.clone(),
+            None => {
+                self.flash(&format!("Register \"{} is empty", register));
+                return;
+            }
+        };
+        if self.seeded {
+            self.set_query("");
+        }
+        self.browsing = false;
+        self.replace_query();
+        self.query.insert_str(self.cursor_index, &text);
+        self.cursor_index += text.len();
+    }
+
+    // Quick Look the selected file without leaving the launcher, or close the preview
+    fn toggle_preview(&mut self) {
+        // the window may have been closed by the user already. Preview's outlives `open`, it
+        // is taken as still open
+        if let Some((_, child)) = &mut self.preview {
+            if self.previewer == "preview" || matches!(child.try_wait(), Ok(None)) {
+                self.close_preview();
+                return;
+            }
+        }
+        if let Some(LauncherResult::File(path)) = &self.selected {
+            match quick_look(path, &self.previewer) {
+                Ok(child) => self.preview = Some((path.clone(), child)),
+                Err(e) => {
+                    self.set_error(&e.to_string());
+                }
+            }
+        }
+    }
+
+    fn close_preview(&mut self) {
+        if let Some((path, mut child)) = self.preview.take() {
+            if self.previewer == "preview" {
+                let _ = child.wait();
+                if let Err(e) = close_in_preview(&path) {
+                    log(&format!("failed to close `{}` in Preview: {}", path, e));
                 }
+                return;
             }
+            let _ = child.kill();
+            let _ = child.wait();
         }
     }
 
     pub fn exit(&mut self) {
+        self.exit_with(None)
+    }
+
+    // Like `exit`, `launched` is printed afterwards with `Config::exit_print`
+    pub fn exit_with(&mut self, launched: Option<&LauncherResult>) {
+        self.close_preview();
+        let _ = self.clear_image();
         if self.running {
+            if self.exit_clear {
+                let backend = self.terminal.backend_mut();
+                execute!(backend, cursor::MoveTo(0, 0), Clear(ClearType::All)).unwrap();
+            }
             disable_raw_mode().unwrap();
             execute!(self.terminal.backend_mut(), LeaveAlternateScreen,).unwrap();
             self.terminal.show_cursor().unwrap();
-            self.running = false
+            self.running = false;
+            if let Some(r) = launched.filter(|_| self.exit_print) {
+                match print_launched(&mut io::stdout(), r) {
+                    Ok(printed) => self.pipe_closed = !printed,
+                    Err(e) => log(&format!("printing the launched result: {}", e)),
+                }
+            }
         }
     }
 
+    // Whether `exit_with` found the pipe it printed to closed
+    pub fn pipe_closed(&self) -> bool {
+        self.pipe_closed
+    }
+
+    pub fn backend(&self) -> &B {
+        self.terminal.backend()
+    }
+
+    pub fn backend_mut(&mut self) -> &mut B {
+        self.terminal.backend_mut()
+    }
+
     pub fn get_query(&self) -> String {
         return self.query.clone();
     }
 
-    pub fn set_prompt(&mut self, prompt: &str) -> &mut App {
+    pub fn set_query(&mut self, query: &str) -> &mut Self {
+        self.query = query.to_string();
+        self.cursor_index = self.query.len();
+        self.completion = false;
+        self.seeded = false;
+        self
+    }
+
+    pub fn get_prompt(&self) -> String {
+        self.prompt.clone()
+    }
+
+    pub fn get_selection(&self) -> Option<usize> {
+        self.list_state.selected()
+    }
+
+    // Since the last key press
+    pub fn idle(&self) -> Duration {
+        self.last_key.elapsed()
+    }
+
+    // Clamped to the list in the next `update`
+    pub fn set_selection(&mut self, index: Option<usize>) -> &mut Self {
+        self.list_state.select(index);
+        self
+    }
+
+    // Shown in the input field's border until the next key press
+    pub fn set_error(&mut self, error: &str) -> &mut Self {
+        self.error = Some(error.to_string());
+        self
+    }
+
+    // What Enter does with the selected result
+    fn launch(&mut self) -> Input {
+        let i = match self.list_state.selected() {
+            Some(i) => i,
+            None => return Input::Continue,
+        };
+        if let Some(r) = &self.selected {
+            if let Some(action) = r.enter_template(&self.enter) {
+                return Input::Primary(i, action);
+            }
+            match r.enter_override(&self.enter) {
+                Some("open-with") => return Input::OpenWith(i),
+                Some(name) => {
+                    if let Some(action) = r.enter_action(name) {
+                        return Input::Primary(i, action);
+                    }
+                }
+                None => {}
+            }
+        }
+        if self
+            .selected
+            .as_ref()
+            .and_then(|r| r.accept_action())
+            .is_some()
+        {
+            return Input::Accept(i);
+        }
+        let confirmation = self.selected.as_ref().and_then(|r| r.confirmation());
+        self.confirm(confirmation, Input::Select(i))
+    }
+
+    // Enter while `building`: the selected result replaces the word being typed, and the
+    // next one can be typed after it
+    fn add_to_query(&mut self) {
+        let content = match &self.selected {
+            Some(r) => r.get_content(),
+            None => return,
+        };
+        self.browsing = false;
+        self.completion = false;
+        let start = self.query.rfind(' ').map_or(0, |i| i + 1);
+        self.query.truncate(start);
+        self.query.push_str(&content);
+        self.query.push(' ');
+        self.cursor_index = self.query.len();
+    }
+
+    // Asks for the action's argument or confirmation first, if it has one
+    fn run_action(&mut self, a: ResultAction) -> Input {
+        if let Some((prompt, text)) = a.action.argument() {
+            self.completion = false;
+            self.argument = Some(Argument {
+                prompt,
+                cursor_index: text.len(),
+                text,
+                result: self
+                    .selected
+                    .as_ref()
+                    .map(|r| r.get_string())
+                    .unwrap_or_default(),
+                action: a.action,
+            });
+            return Input::Continue;
+        }
+        self.confirm(a.confirmation, Input::Action(a.action))
+    }
+
+    // Moves in the actions menu, Enter runs the action and Esc goes back to the results
+    fn actions_menu_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Input {
+        let mut m = match self.actions_menu.take() {
+            Some(m) => m,
+            None => return Input::Continue,
+        };
+        let len = m.actions.len() + 1;
+        match code {
+            KeyCode::Enter if m.selected == 0 => return self.launch(),
+            KeyCode::Enter => return self.run_action(m.actions.swap_remove(m.selected - 1)),
+            KeyCode::Up => m.selected = (m.selected + len - 1) % len,
+            KeyCode::Down | KeyCode::Tab => m.selected = (m.selected + 1) % len,
+            KeyCode::Esc => return Input::Continue,
+            _ if self.keymap.is("actions", code, modifiers)
+                || self.keymap.is("quit", code, modifiers) =>
+            {
+                return Input::Continue
+            }
+            _ => {}
+        }
+        self.actions_menu = Some(m);
+        Input::Continue
+    }
+
+    // The `quit` key, by `Config::quit_key`
+    fn quit(&mut self) -> Input {
+        match self.quit_key.as_str() {
+            // like a terminal copying what is selected, Esc quits instead
+            "copy" => match &self.selected {
+                Some(r) => Input::Action(Action::CopyText(r.text())),
+                None => {
+                    self.flash("Nothing selected, Esc quits");
+                    Input::Continue
+                }
+            },
+            "double" => match self.quit_pressed.take() {
+                Some(t) if t.elapsed() < FLASH => Input::Exit,
+                _ => {
+                    self.quit_pressed = Some(Instant::now());
+                    self.flash("Press again to quit");
+                    Input::Continue
+                }
+            },
+            _ => Input::Exit,
+        }
+    }
+
+    // Edits the argument being typed. Enter runs its action with it, Esc and Ctrl+C cancel
+    fn argument_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Input {
+        let mut a = match self.argument.take() {
+            Some(a) => a,
+            None => return Input::Continue,
+        };
+        let before = a.text[..a.cursor_index].char_indices().next_back();
+        match code {
+            KeyCode::Enter => return Input::Action(a.action.with_argument(a.text)),
+            KeyCode::Esc => return Input::Continue,
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                return Input::Continue
+            }
+            // not typed, e.g. `ctrl+x`
+            KeyCode::Char(_) if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+            }
+            KeyCode::Char(ch) => {
+                a.text.insert(a.cursor_index, ch);
+                a.cursor_index += ch.len_utf8();
+            }
+            KeyCode::Backspace => {
+                if let Some((i, _)) = before {
+                    a.text.remove(i);
+                    a.cursor_index = i;
+                }
+            }
+            KeyCode::Delete if a.cursor_index < a.text.len() => {
+                a.text.remove(a.cursor_index);
+            }
+            KeyCode::Left => {
+                if let Some((i, _)) = before {
+                    a.cursor_index = i;
+                }
+            }
+            KeyCode::Right => {
+                if let Some(ch) = a.text[a.cursor_index..].chars().next() {
+                    a.cursor_index += ch.len_utf8();
+                }
+            }
+            KeyCode::Home => a.cursor_index = 0,
+            KeyCode::End => a.cursor_index = a.text.len(),
+            _ => {}
+        }
+        self.argument = Some(a);
+        Input::Continue
+    }
+
+    // Ask for confirmation first if there is a message for it
+    fn confirm(&mut self, message: Option<String>, input: Input) -> Input {
+        match (message, &self.selected) {
+            (Some(message), Some(r)) => {
+                self.confirming = Some(Confirm {
+                    message,
+                    result: r.get_string(),
+                    input,
+                });
+                Input::Continue
+            }
+            _ => input,
+        }
+    }
+
+    // Byte index of the character before the cursor.
+    // `cursor_index` is always on a character boundary so multibyte input can be edited.
+    // The terminal shows input method compositions itself, only committed text reaches us
+    fn prev_char_boundary(&self) -> Option<usize> {
+        self.query[..self.cursor_index]
+            .char_indices()
+            .next_back()
+            .map(|(i, _)| i)
+    }
+
+    // Headings of grouped results, see `backend::group_results`. `None` for a plain list
+    pub fn set_groups(&mut self, groups: Option<Vec<(ResultKind, usize, usize)>>) -> &mut Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn set_top_hit(&mut self, top_hit: bool) -> &mut Self {
+        self.top_hit = top_hit;
+        self
+    }
+
+    // Shown in the input field's border for a moment
+    pub fn flash(&mut self, msg: &str) -> &mut Self {
+        self.flash = Some((msg.to_string(), Instant::now()));
+        self
+    }
+
+    pub fn set_prompt(&mut self, prompt: &str) -> &mut Self {
         self.prompt = prompt.to_string();
         self
     }
 
+    // Query selected so that typing replaces it, with a hint like `(+2 lines)` after it
+    pub fn seed_query(&mut self, query: &str, hint: Option<String>) -> &mut Self {
+        self.set_query(query);
+        self.seeded = true;
+        self.seed_hint = hint;
+        self
+    }
+
+    // Name of the mode other than `all`
+    pub fn set_mode(&mut self, mode: Option<&str>) -> &mut Self {
+        self.mode = mode.map(|m| m.to_string());
+        self
+    }
+
+    // Levels of the stages gone through, drawn above the input field. `None` hides it
+    pub fn set_breadcrumb(&mut self, levels: Option<Vec<String>>) -> &mut Self {
+        self.breadcrumb = levels.filter(|l| !l.is_empty());
+        self
+    }
+
+    // Modes drawn as tabs above the results, `active` highlighted. `None` hides them
+    pub fn set_tabs(&mut self, tabs: Option<(Vec<String>, usize)>) -> &mut Self {
+        self.tabs = tabs;
+        self
+    }
+
+    pub fn select_tab(&mut self, active: usize) -> &mut Self {
+        if let Some((_, a)) = &mut self.tabs {
+            *a = active;
+        }
+        self
+    }
+
+    // e.g. `Menu>` while the menu keyword is typed, the stage's prompt for `None`
+    pub fn set_mode_prompt(&mut self, prompt: Option<String>) -> &mut Self {
+        self.mode_prompt = prompt;
+        self
+    }
+
+    // What Enter will do shows in the prompt
+    fn shown_prompt(&self) -> &str {
+        if self.confirming.is_some() {
+            "Confirm>"
+        } else if let Some(a) = &self.argument {
+            a.prompt
+        } else {
+            self.mode_prompt.as_deref().unwrap_or(&self.prompt)
+        }
+    }
+
+    // An empty query lists everything while filtering
+    pub fn set_filtering(&mut self, filtering: bool) -> &mut Self {
+        self.filtering = filtering;
+        self
+    }
+
+    // Up and Down move by a row of the grid, Left and Right to the previous and next result
+    fn move_in_grid(&mut self, code: KeyCode) {
+        let (columns, start) = self.grid;
+        let i = match self.list_state.selected() {
+            Some(i) => i,
+            None => return,
+        };
+        let last = self.list_len.saturating_sub(1);
+        let i = match code {
+            // the top hit is above the grid
+            KeyCode::Up if i < start + columns && start > 0 => 0,
+            KeyCode::Up if i < start + columns => i,
+            KeyCode::Up => i - columns,
+            KeyCode::Down if i < start => start.min(last),
+            KeyCode::Down if i + columns <= last => i + columns,
+            KeyCode::Left => i.saturating_sub(1),
+            KeyCode::Right => (i + 1).min(last),
+            _ => i,
+        };
+        self.list_state.select(Some(i));
+    }
+
     fn fix_selection(&mut self) {
         if self.list_len > 0 {
             match self.list_state.selected() {
@@ -250,12 +1929,249 @@ impl App {
     }
 }
 
-impl Drop for App {
+impl<B: Backend + Write> Drop for App<B> {
     fn drop(&mut self) {
         self.exit()
     }
 }
 
+// How many results each kind of source contributed, in the order they are listed, as bars
+// in `width` columns. `#` instead of blocks in plain mode
+fn histogram(list: &[LauncherResult], width: u16, plain: bool) -> Paragraph<'static> {
+    let mut counts: Vec<(String, usize)> = vec![];
+    for r in list {
+        let label = r.get_string();
+        let label = label
+            .split('|')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        match counts.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((label, 1)),
+        }
+    }
+    let max = counts.iter().map(|(_, n)| *n).max().unwrap_or(1);
+    let lines = counts
+        .into_iter()
+        .map(|(label, n)| {
+            // e.g. `File ████ 12`
+            let room = (width as usize).saturating_sub(6 + n.to_string().len());
+            let bar = if plain { "#" } else { "█" }.repeat((n * room).div_ceil(max));
+            Spans::from(vec![
+                Span::raw(format!("{:<4} ", label)),
+                Span::styled(bar, Style::default().fg(Color::Cyan)),
+                Span::raw(format!(" {}", n)),
+            ])
+        })
+        .collect::<Vec<Spans>>();
+    Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Sources"))
+}
+
+// e.g. `Files (12)`, or `Files (5 of 12)` when capped
+fn group_heading(groups: &[(ResultKind, usize, usize)], kind: ResultKind) -> ListItem<'static> {
+    let title = match groups.iter().find(|(k, ..)| *k == kind) {
+        Some((_, shown, total)) if shown < total => {
+            format!("{} ({} of {})", kind.title(), shown, total)
+        }
+        Some((_, _, total)) => format!("{} ({})", kind.title(), total),
+        None => kind.title().to_string(),
+    };
+    ListItem::new(Span::styled(
+        title,
+        Style::default().add_modifier(Modifier::BOLD),
+    ))
+}
+
+// Sets the style of the cells, e.g. of the selection marker
+struct Restyle(Style);
+
+impl Widget for Restyle {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        buf.set_style(area, self.0);
+    }
+}
+
+// `Config::theme` with the colors parsed
+struct Theme {
+    selection: Style,
+    marker: Option<Style>, // the selection's if `None`
+    match_exact: Style,
+    match_fuzzy: Style,
+}
+
+impl Theme {
+    fn new(theme: &ThemeConfig) -> Theme {
+        let color = |name: &str, field: &str| {
+            let color = parse_color(name);
+            if color.is_none() && !name.is_empty() {
+                log(&format!("invalid color `{}` for `theme.{}`", name, field));
+            }
+            color
+        };
+        let mut selection = Style::default().add_modifier(Modifier::BOLD);
+        if let Some(fg) = color(&theme.selection_fg, "selection_fg") {
+            selection = selection.fg(fg);
+        }
+        if let Some(bg) = color(&theme.selection_bg, "selection_bg") {
+            selection = selection.bg(bg);
+        }
+        let style = |spec: &str, field: &str| {
+            parse_style(spec).unwrap_or_else(|| {
+                log(&format!("invalid style `{}` for `theme.{}`", spec, field));
+                Style::default()
+            })
+        };
+        Theme {
+            selection,
+            marker: color(&theme.marker, "marker").map(|c| selection.fg(c)),
+            match_exact: style(&theme.match_exact, "match_exact"),
+            match_fuzzy: style(&theme.match_fuzzy, "match_fuzzy"),
+        }
+    }
+}
+
+// Words separated by spaces, `bold`, `dim`, `italic`, `underline` or `reversed`, and a color
+// for `parse_color`. Nothing if empty
+fn parse_style(spec: &str) -> Option<Style> {
+    let mut style = Style::default();
+    for word in spec.split_whitespace() {
+        style = match word.to_lowercase().as_str() {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "dim" => style.add_modifier(Modifier::DIM),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underline" => style.add_modifier(Modifier::UNDERLINED),
+            "reversed" => style.add_modifier(Modifier::REVERSED),
+            _ => style.fg(parse_color(word)?),
+        };
+    }
+    Some(style)
+}
+
+// `text` in `base`, with `style` on top for the characters at `indices`
+fn highlighted(text: &str, indices: &[usize], base: Style, style: Style) -> Vec<Span<'static>> {
+    let mut spans: Vec<Span> = vec![];
+    let mut run = String::new();
+    let mut in_match = false;
+    let shown = |in_match: bool| if in_match { base.patch(style) } else { base };
+    for (i, ch) in text.chars().enumerate() {
+        let is_match = indices.contains(&i);
+        if is_match != in_match && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), shown(in_match)));
+        }
+        in_match = is_match;
+        run.push(ch);
+    }
+    spans.push(Span::styled(run, shown(in_match)));
+    spans
+}
+
+// The version, and the config file read or that there was none
+fn footer(config: &Config) -> String {
+    let source = match &config.loaded_from {
+        Some(path) => match path.strip_prefix(HOME_PATH.as_str()) {
+            Some(rest) => format!("~{}", rest),
+            None => path.clone(),
+        },
+        None => format!("defaults, no valid {}", CONFIG_PATH.as_str()),
+    };
+    format!("launcher {} | {}", env!("CARGO_PKG_VERSION"), source)
+}
+
+// Named colors like `light_blue`, `#rrggbb` or a 256 color palette index
+fn parse_color(name: &str) -> Option<Color> {
+    let name = name.to_lowercase().replace(['_', '-', ' '], "");
+    if let Some(hex) = name.strip_prefix('#').filter(|h| h.len() == 6) {
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Ok(i) = name.parse() {
+        return Some(Color::Indexed(i));
+    }
+    Some(match name.as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+// Drops all styling and replaces the box drawing of borders with ASCII
+struct Plain;
+
+impl Widget for Plain {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                cell.set_style(Style::reset());
+                let ascii = match cell.symbol.as_str() {
+                    "─" => "-",
+                    "│" => "|",
+                    "┌" | "┐" | "└" | "┘" => "+",
+                    _ => continue,
+                };
+                cell.set_symbol(ascii);
+            }
+        }
+    }
+}
+
+// `text` in lines of `width` columns at most, cut anywhere since paths have no spaces
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    let mut used = 0;
+    for ch in text.chars() {
+        let w = ch.width().unwrap_or(0);
+        if used + w > width.max(1) && used > 0 {
+            lines.push(String::new());
+            used = 0;
+        }
+        lines.last_mut().unwrap().push(ch);
+        used += w;
+    }
+    lines
+}
+
+// `text` and `marker` at the right end of `width` columns, `text` cut if too long
+fn align_right(text: &str, marker: &str, width: usize) -> String {
+    let space = width.saturating_sub(marker.width());
+    let mut aligned = String::new();
+    let mut used = 0;
+    for ch in text.chars() {
+        let w = ch.width().unwrap_or(0);
+        if used + w > space {
+            break;
+        }
+        aligned.push(ch);
+        used += w;
+    }
+    aligned + &" ".repeat(space - used) + marker
+}
+
+// Prints `r` for `Config::exit_print`. False if the reader went away, e.g. `launcher | head`
+pub fn print_launched(out: &mut impl Write, r: &LauncherResult) -> io::Result<bool> {
+    match writeln!(out, "{}", r.get_content()).and_then(|_| out.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+        result => result.map(|_| true),
+    }
+}
+
 fn cleanup_terminal() {
     let mut stdout = io::stdout();
 
@@ -0,0 +1,169 @@
+// Kana to romaji, so `surakku` finds `スラック`. Kanji have no single reading and stay as they are
+
+// Katakana from ア, in Unicode order. Small kana are handled by `to_romaji`
+const KATAKANA: &[(char, &str)] = &[
+    ('ア', "a"),
+    ('イ', "i"),
+    ('ウ', "u"),
+    ('エ', "e"),
+    ('オ', "o"),
+    ('カ', "ka"),
+    ('ガ', "ga"),
+    ('キ', "ki"),
+    ('ギ', "gi"),
+    ('ク', "ku"),
+    ('グ', "gu"),
+    ('ケ', "ke"),
+    ('ゲ', "ge"),
+    ('コ', "ko"),
+    ('ゴ', "go"),
+    ('サ', "sa"),
+    ('ザ', "za"),
+    ('シ', "shi"),
+    ('ジ', "ji"),
+    ('ス', "su"),
+    ('ズ', "zu"),
+    ('セ', "se"),
+    ('ゼ', "ze"),
+    ('ソ', "so"),
+    ('ゾ', "zo"),
+    ('タ', "ta"),
+    ('ダ', "da"),
+    ('チ', "chi"),
+    ('ヂ', "ji"),
+    ('ツ', "tsu"),
+    ('ヅ', "zu"),
+    ('テ', "te"),
+    ('デ', "de"),
+    ('ト', "to"),
+    ('ド', "do"),
+    ('ナ', "na"),
+    ('ニ', "ni"),
+    ('ヌ', "nu"),
+    ('ネ', "ne"),
+    ('ノ', "no"),
+    ('ハ', "ha"),
+    ('バ', "ba"),
+    ('パ', "pa"),
+    ('ヒ', "hi"),
+    ('ビ', "bi"),
+    ('ピ', "pi"),
+    ('フ', "fu"),
+    ('ブ', "bu"),
+    ('プ', "pu"),
+    ('ヘ', "he"),
+    ('ベ', "be"),
+    ('ペ', "pe"),
+    ('ホ', "ho"),
+    ('ボ', "bo"),
+    ('ポ', "po"),
+    ('マ', "ma"),
+    ('ミ', "mi"),
+    ('ム', "mu"),
+    ('メ', "me"),
+    ('モ', "mo"),
+    ('ヤ', "ya"),
+    ('ユ', "yu"),
+    ('ヨ', "yo"),
+    ('ラ', "ra"),
+    ('リ', "ri"),
+    ('ル', "ru"),
+    ('レ', "re"),
+    ('ロ', "ro"),
+    ('ワ', "wa"),
+    ('ヰ', "i"),
+    ('ヱ', "e"),
+    ('ヲ', "o"),
+    ('ン', "n"),
+    ('ヴ', "vu"),
+];
+
+// Hiragana are katakana shifted down by 0x60
+fn katakana(c: char) -> char {
+    match c {
+        'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(katakana(c), 'ァ'..='ヺ' | 'ー')
+}
+
+// Hepburn romaji of the kana in `text`, other characters are kept. `None` without kana
+pub fn to_romaji(text: &str) -> Option<String> {
+    if !text.chars().any(is_kana) {
+        return None;
+    }
+    let mut romaji = String::new();
+    let mut double = false; // after a small ッ
+    let mut last = String::new(); // the syllable before
+    for c in text.chars().map(katakana) {
+        let syllable = match c {
+            'ッ' => {
+                double = true;
+                continue;
+            }
+            // a long vowel repeats the last one
+            'ー' => match romaji.chars().last() {
+                Some(v @ ('a' | 'i' | 'u' | 'e' | 'o')) => v.to_string(),
+                _ => String::new(),
+            },
+            // キャ is kya, シャ sha and ジャ ja
+            'ャ' | 'ュ' | 'ョ' if last.len() > 1 && last.ends_with('i') => {
+                let vowel = match c {
+                    'ャ' => "a",
+                    'ュ' => "u",
+                    _ => "o",
+                };
+                romaji.pop();
+                if romaji.ends_with("sh") || romaji.ends_with("ch") || romaji.ends_with('j') {
+                    vowel.to_string()
+                } else {
+                    format!("y{}", vowel)
+                }
+            }
+            // ファ is fa and ティ ti, the small vowel replaces the one before
+            'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ' => {
+                let vowel = match c {
+                    'ァ' => "a",
+                    'ィ' => "i",
+                    'ゥ' => "u",
+                    'ェ' => "e",
+                    _ => "o",
+                };
+                match last.as_str() {
+                    // ウィ is wi, ヴィ vi
+                    "u" => {
+                        romaji.pop();
+                        format!("w{}", vowel)
+                    }
+                    l if l.len() > 1 && l.ends_with(['a', 'i', 'u', 'e', 'o']) => {
+                        romaji.pop();
+                        vowel.to_string()
+                    }
+                    _ => vowel.to_string(),
+                }
+            }
+            'ャ' => "ya".to_string(),
+            'ュ' => "yu".to_string(),
+            'ョ' => "yo".to_string(),
+            'ヮ' => "wa".to_string(),
+            _ => match KATAKANA.iter().find(|(k, _)| *k == c) {
+                Some((_, r)) => r.to_string(),
+                None => c.to_string(),
+            },
+        };
+        if std::mem::take(&mut double) {
+            // ッチ is tchi
+            match syllable.chars().next() {
+                Some('c') => romaji.push('t'),
+                Some(c) if c.is_ascii_alphabetic() && !"aiueon".contains(c) => romaji.push(c),
+                _ => {}
+            }
+        }
+        romaji.push_str(&syllable);
+        last = syllable;
+    }
+    Some(romaji)
+}
@@ -0,0 +1,167 @@
+// Which result was picked for a query, so that it comes first the next time the query (or
+// most of it) is typed
+use crate::backend::LauncherResult;
+use crate::state;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Selections kept, the least used are forgotten first
+const MAX_LEARNED: usize = 500;
+
+// Of the weight after decay, times how much of the learned query was typed. Below it the
+// learned result ranks normally, so `s` doesn't jump to what was picked for `safari`
+const MIN_BOOST: f64 = 0.5;
+
+lazy_static! {
+    // `None` until loaded
+    static ref LEARNED: Mutex<Option<Vec<Selection>>> = Mutex::new(None);
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct Learned {
+    selections: Vec<Selection>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Selection {
+    pub query: String,  // lower case
+    pub result: String, // `get_string` of the result
+    pub weight: f64,    // picks, halved every `half_life_days`
+    pub time: u64,      // of the last pick, in seconds since the epoch
+}
+
+impl Selection {
+    fn weight_at(&self, now: u64, half_life_days: f64) -> f64 {
+        let days = now.saturating_sub(self.time) as f64 / 86400.0;
+        if half_life_days <= 0.0 {
+            return self.weight;
+        }
+        self.weight * 0.5f64.powf(days / half_life_days)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn with_learned<T>(f: impl FnOnce(&mut Vec<Selection>) -> T) -> T {
+    let mut learned = LEARNED.lock().unwrap();
+    let learned = learned.get_or_insert_with(|| {
+        state::store()
+            .load::<Learned>("learned")
+            .unwrap_or_default()
+            .selections
+    });
+    f(learned)
+}
+
+// Remember that `result` was picked for `query`
+pub fn learn(
+    query: &str,
+    result: &LauncherResult,
+    half_life_days: f64,
+) -> Result<(), Box<dyn Error>> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Ok(());
+    }
+    let result = result.get_string();
+    let now = now();
+    let selections = with_learned(|learned| {
+        match learned
+            .iter_mut()
+            .find(|s| s.query == query && s.result == result)
+        {
+            Some(s) => {
+                s.weight = s.weight_at(now, half_life_days) + 1.0;
+                s.time = now;
+            }
+            None => learned.push(Selection {
+                query,
+                result,
+                weight: 1.0,
+                time: now,
+            }),
+        }
+        if learned.len() > MAX_LEARNED {
+            learned.sort_by(|a, b| {
+                b.weight_at(now, half_life_days)
+                    .total_cmp(&a.weight_at(now, half_life_days))
+            });
+            learned.truncate(MAX_LEARNED);
+        }
+        learned.clone()
+    });
+    state::store().save("learned", &Learned { selections })
+}
+
+// Moves the result learned for `query` to the front. Returns whether one was moved
+pub fn boost(query: &str, results: &mut Vec<LauncherResult>, half_life_days: f64) -> bool {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() || results.is_empty() {
+        return false;
+    }
+    let now = now();
+    let typed = query.chars().count() as f64;
+    let best = with_learned(|learned| {
+        learned
+            .iter()
+            .filter(|s| s.query.starts_with(&query))
+            .map(|s| {
+                let covered = typed / s.query.chars().count() as f64;
+                (s.weight_at(now, half_life_days) * covered, s.result.clone())
+            })
+            .filter(|(boost, _)| *boost >= MIN_BOOST)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+    });
+    let best = match best {
+        Some((_, best)) => best,
+        None => return false,
+    };
+    match results.iter().position(|r| r.get_string() == best) {
+        Some(i) if i > 0 => {
+            let r = results.remove(i);
+            results.insert(0, r);
+            true
+        }
+        _ => false,
+    }
+}
+
+// Everything learned, most used first
+pub fn selections(half_life_days: f64) -> Vec<(Selection, f64)> {
+    let now = now();
+    let mut selections = with_learned(|learned| {
+        learned
+            .iter()
+            .map(|s| (s.clone(), s.weight_at(now, half_life_days)))
+            .collect::<Vec<(Selection, f64)>>()
+    });
+    selections.sort_by(|a, b| b.1.total_cmp(&a.1));
+    selections
+}
+
+// How much each result was picked lately, whatever the query, by `get_string`
+pub fn frecency(half_life_days: f64) -> HashMap<String, f64> {
+    let mut frecency = HashMap::new();
+    for (s, weight) in selections(half_life_days) {
+        *frecency.entry(s.result).or_insert(0.0) += weight;
+    }
+    frecency
+}
+
+// Forgets everything, returns how many selections there were
+pub fn reset() -> io::Result<usize> {
+    let count = with_learned(|learned| std::mem::take(learned).len());
+    state::store().remove("learned")?;
+    Ok(count)
+}
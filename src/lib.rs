@@ -1,4 +1,19 @@
+// the code's own style: explicit returns, `new` without `Default`
+#![allow(
+    clippy::needless_return,
+    clippy::new_without_default,
+    clippy::useless_format,
+    clippy::needless_borrow
+)]
 pub mod backend;
+pub mod calc;
+pub mod dictionary;
 pub mod frontend;
+pub mod kana;
+pub mod learned;
+pub mod markdown;
+pub mod menu;
+pub mod preview;
+pub mod state;
 #[macro_use]
 extern crate lazy_static;
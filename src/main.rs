@@ -1,14 +1,45 @@
+// the code's own style, see lib.rs
+#![allow(clippy::needless_return, clippy::explicit_auto_deref)]
 use launcher::backend::*;
 use launcher::frontend::*;
-use std::time::Duration;
+use launcher::learned;
+use launcher::state::{self, StateStore};
 use std::{
+    collections::HashSet,
+    env,
     error::Error,
     io,
     io::Read,
+    path::Path,
+    process,
     sync::{mpsc, Arc, Mutex, TryLockError},
     thread,
+    time::{Duration, Instant},
 };
 
+// Deepest `Input::OpenWith` chain
+const MAX_STAGES: usize = 4;
+
+// Results are not refreshed until no key was pressed for this long
+const TYPING: Duration = Duration::from_millis(500);
+
+// State of a stage to go back to
+struct Stage {
+    query: String,
+    prompt: String,
+    scope: Scope,
+    selection: Option<usize>,
+}
+
+// Filter mode: the query narrows down `results` instead of searching
+struct Filter {
+    results: Arc<Vec<LauncherResult>>,
+    keys: Vec<String>, // `filter_keys` of `results`
+    stage: Stage,      // to go back to
+    query: String,
+    filtered: Arc<Vec<LauncherResult>>,
+}
+
 macro_rules! mutex {
     ($l:ident $op:tt $r:expr) => {
         { *$l.lock().unwrap() $op $r; }
@@ -16,18 +47,67 @@ macro_rules! mutex {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut app = App::init("Query>")?;
+    // `launcher ignore list`, `launcher ignore remove <pattern>`
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.first().is_some_and(|a| a == "ignore") {
+        return ignore_command(&args[1..]);
+    }
+    let mut config = Config::from_file(&CONFIG_PATH);
+    state::configure(StateStore::new(&config.state_dir, &config.state_format));
+    // `launcher learned list`, `launcher learned reset`
+    if args.first().is_some_and(|a| a == "learned") {
+        return learned_command(&args[1..], &config);
+    }
+    // `launcher state dir`, `launcher state reset`
+    if args.first().is_some_and(|a| a == "state") {
+        return state_command(&args[1..]);
+    }
+    config.plain |= env::args().any(|a| a == "--plain");
+    config.load_env();
+    let config = Arc::new(config);
+    let mut app = App::init("Query>", &config)?;
+    let unknown = config
+        .modes
+        .iter()
+        .filter(|m| Scope::from_mode(m).is_none())
+        .map(|m| format!("`{}`", m))
+        .collect::<Vec<String>>();
+    if !unknown.is_empty() {
+        app.set_error(&format!("Unknown modes in `modes`: {}", unknown.join(", ")));
+    }
+    let enter_errors = config.enter_errors();
+    if !enter_errors.is_empty() {
+        app.set_error(&format!("In `[enter]`: {}", enter_errors.join("; ")));
+    }
+    // switched between at the first stage
+    let modes = config
+        .modes
+        .iter()
+        .filter_map(|m| Some((m.clone(), Scope::from_mode(m)?)))
+        .collect::<Vec<(String, Scope)>>();
+    let mut mode = 0;
+    if config.tabs && modes.len() > 1 {
+        let names = modes.iter().map(|(name, _)| name.clone()).collect();
+        app.set_tabs(Some((names, mode)));
+    }
+    // nothing is read from the clipboard unless asked to
+    if config.query_from_clipboard || env::args().any(|a| a == "--query-from-clipboard") {
+        if let Some((query, more)) = read_clipboard(config.clipboard_max_len) {
+            let hint = (more > 0).then(|| format!("(+{} lines)", more));
+            app.seed_query(&query, hint);
+        }
+    }
 
     let cache = Arc::new(Mutex::new(Cache::new()));
     let backend_cache = Arc::clone(&cache);
-    let config = Arc::new(Config::from_file(&CONFIG_PATH));
     let backend_config = Arc::clone(&config);
-    let (query_tx, query_rx) = mpsc::channel::<String>();
+    let (query_tx, query_rx) = mpsc::channel::<(String, Scope)>();
     let (select_tx, select_rx) = mpsc::channel::<LauncherResult>();
 
     // wait for launching result
+    let select_config = Arc::clone(&config);
     let selection = thread::spawn(move || {
-        let config = Arc::clone(&config);
+        let config = select_config;
         let magic_cookie = new_magic_cookie().unwrap();
         loop {
             if let Ok(r) = select_rx.recv() {
@@ -45,50 +125,460 @@ fn main() -> Result<(), Box<dyn Error>> {
         let config = Arc::clone(&backend_config);
         mutex!(backend_cache = Cache::init(&config));
 
-        while let Ok(s) = query_rx.recv() {
-            if !s.is_empty() {
-                let config = Arc::clone(&config);
-                let backend_cache = Arc::clone(&backend_cache);
-                thread::spawn(move || {
-                    let mut new_cache = {
-                        let inner = backend_cache.lock().unwrap().clone();
-                        Query::from(s.as_str()).parse(&config, inner).unwrap()
-                    };
-                    let mut inner = backend_cache.lock().unwrap();
-                    for f in new_cache.file_entries {
-                        inner.file_entries.insert(f);
-                    }
-                    if let Some(r) = new_cache.search_results.remove(s.as_str()) {
-                        inner.search_results.insert(s, r);
-                    }
-                });
+        // the query is sent every frame, a round starts when it changes
+        let generations = Generations::default();
+        let mut round = generations.next();
+        let mut last: Option<(String, Scope)> = None;
+        while let Ok((s, scope)) = query_rx.recv() {
+            if last.as_ref() != Some(&(s.clone(), scope.clone())) {
+                round = generations.next();
+                last = Some((s.clone(), scope.clone()));
             }
+            let config = Arc::clone(&config);
+            let backend_cache = Arc::clone(&backend_cache);
+            let round = round.clone();
+            thread::spawn(move || {
+                let new_cache = {
+                    let inner = backend_cache.lock().unwrap().clone();
+                    Query::with_scope(s.as_str(), scope)
+                        .parse_in(&config, inner, &round)
+                        .unwrap()
+                };
+                // results of an older query arriving late are dropped
+                backend_cache.lock().unwrap().merge(new_cache, &round);
+            });
         }
     });
 
     // UI
     let mut results: Arc<Vec<LauncherResult>> = Arc::new(vec![]);
+    let mut top_hit = false;
+    // selection and its `identity` while refreshing
+    let mut keep: Option<(Option<usize>, Option<String>)> = None;
+    // the query's key, and whether its results are the recent ones still to select one of
+    let mut recents: (Option<String>, bool) = (None, false);
+    // group shown in full, with the key of the query it was expanded for
+    let mut expanded: Option<(String, ResultKind)> = None;
+    // run before the first input
+    let mut repeat = env::args()
+        .any(|a| a == "--repeat-last")
+        .then_some(Input::RepeatLast);
+    let mut scope = Scope::All;
+    let mut stages: Vec<Stage> = vec![];
+    let mut filter: Option<Filter> = None;
     loop {
-        let mut index = None;
-        query_tx.send(app.get_query()).unwrap();
-        results = match cache.try_lock() {
-            Ok(r) => r.get_results(&app.get_query()).unwrap_or(results),
-            Err(r) => {
-                if let TryLockError::WouldBlock = r {
-                    results
+        let query = app.get_query();
+        // what is searched, the query shown stays as typed
+        let normalized = normalize_query(&query);
+        let keyword = filter.is_none() && scope == Scope::All;
+        // where the stages led, from the first
+        let levels = stages
+            .iter()
+            .map(|s| s.scope.title())
+            .chain([scope.title()])
+            .chain(filter.as_ref().map(|_| "Filter".to_string()))
+            .collect::<Vec<String>>();
+        app.set_breadcrumb((levels.len() > 1).then_some(levels));
+        app.set_mode_prompt(keyword.then(|| mode_prompt(&normalized, &config)).flatten());
+        // transient results go once they expire, also from those being filtered. Not while
+        // a search holds the cache, the next round prunes them
+        let expired = match cache.try_lock() {
+            Ok(mut c) => c.prune(Instant::now()),
+            Err(_) => HashSet::new(),
+        };
+        if let Some(f) = filter.as_mut().filter(|_| !expired.is_empty()) {
+            let kept = |r: &&LauncherResult| !expired.contains(&r.get_string());
+            f.results = Arc::new(f.results.iter().filter(kept).cloned().collect());
+            f.filtered = Arc::new(f.filtered.iter().filter(kept).cloned().collect());
+            f.keys = filter_keys(&f.results);
+        }
+        if let Some(f) = &mut filter {
+            if f.query != normalized {
+                f.filtered = Arc::new(filter_results(
+                    &f.results,
+                    &f.keys,
+                    &normalized,
+                    &config.matcher,
+                ));
+                f.query = normalized.clone();
+            }
+            results = Arc::clone(&f.filtered);
+            top_hit = false;
+            app.set_groups(None);
+        } else {
+            let key = scope.key(&normalized);
+            if recents.0.as_ref() != Some(&key) {
+                let listed = normalized.is_empty() && scope == Scope::All;
+                recents = (Some(key.clone()), listed && config.empty_query == "recent");
+            }
+            // live results are searched again, not while typing
+            if let Some(interval) = refresh_interval(&normalized).filter(|_| app.idle() >= TYPING) {
+                if cache.lock().unwrap().expire(&key, interval) {
+                    let selection = app.get_selection();
+                    let selected = selection.and_then(|i| results.get(i));
+                    keep = Some((
+                        selection,
+                        selected.and_then(|r| r.identity()).map(String::from),
+                    ));
+                }
+            }
+            query_tx.send((normalized.clone(), scope.clone())).unwrap();
+            results = match cache.try_lock() {
+                Ok(r) => match r.get_results(&key) {
+                    Some(new) => {
+                        top_hit = r.is_top_hit(&key);
+                        let new = if config.group_results {
+                            let full = expanded.as_ref().filter(|(k, _)| *k == key);
+                            let (grouped, counts) =
+                                group_results(&new, config.group_cap, full.map(|(_, kind)| *kind));
+                            app.set_groups(Some(counts));
+                            Arc::new(grouped)
+                        } else {
+                            new
+                        };
+                        // on start and whenever the query is cleared
+                        if std::mem::take(&mut recents.1) {
+                            if let Some(i) = recent_selection(&config.recent_select, new.len()) {
+                                app.set_selection(Some(i));
+                            }
+                        }
+                        // e.g. the same result stays selected after a refresh
+                        if let Some((index, identity)) = keep.take() {
+                            let policy = &config.refresh_selection;
+                            app.set_selection(refreshed_selection(
+                                policy,
+                                identity.as_deref(),
+                                index,
+                                &new,
+                            ));
+                        }
+                        new
+                    }
+                    None => results,
+                },
+                Err(r) => {
+                    if let TryLockError::WouldBlock = r {
+                        results
+                    } else {
+                        panic!("{:?}", r);
+                    }
+                }
+            };
+        }
+        let input = match repeat.take() {
+            Some(input) => input,
+            None => app
+                .set_top_hit(top_hit)
+                .update(&results)?
+                .wait_input()
+                .unwrap(),
+        };
+        // what the query led to, see `learned`
+        let picked = match &input {
+            Input::Select(i) | Input::SelectCopy(i) | Input::Primary(i, _)
+                if scope == Scope::All && filter.is_none() =>
+            {
+                results.get(*i).cloned()
+            }
+            _ => None,
+        };
+        let selected = match input {
+            Input::Continue => continue,
+            Input::Exit => None,
+            Input::Select(i) => Some(scope.apply(results[i].clone())),
+            Input::SelectCopy(i) => {
+                if let Err(e) = copy_to_clipboard(&results[i].get_content()) {
+                    app.set_error(&e.to_string());
+                    continue;
+                }
+                Some(scope.apply(results[i].clone()))
+            }
+            Input::Action(action) => {
+                match action.run(&config) {
+                    Ok(msg) => {
+                        if let Action::Trash(path)
+                        | Action::Ignore(path)
+                        | Action::Rename(path, _)
+                        | Action::Move(path, _) = &action
+                        {
+                            cache.lock().unwrap().remove_path(path);
+                            // results are not searched again while filtering
+                            if let Some(f) = &mut filter {
+                                let kept = |r: &&LauncherResult| r.identity() != Some(path);
+                                f.results =
+                                    Arc::new(f.results.iter().filter(kept).cloned().collect());
+                                f.filtered =
+                                    Arc::new(f.filtered.iter().filter(kept).cloned().collect());
+                                f.keys = filter_keys(&f.results);
+                            }
+                        }
+                        // the text went to another app, the launcher is done
+                        if let Action::Insert(_) = &action {
+                            app.exit_with(None);
+                            after_exit(&config.on_accept, &config);
+                            break;
+                        }
+                        app.flash(&msg)
+                    }
+                    Err(e) => app.set_error(&e.to_string()),
+                };
+                continue;
+            }
+            Input::Accept(i) => {
+                if let Some(action) = results[i].accept_action() {
+                    match action.run(&config) {
+                        Ok(msg) => {
+                            results[i].accepted(&config);
+                            // e.g. `ans` changed
+                            let mut cache = cache.lock().unwrap();
+                            cache.search_results.clear();
+                            cache.top_hits.clear();
+                            app.flash(&msg)
+                        }
+                        Err(e) => app.set_error(&e.to_string()),
+                    };
+                }
+                continue;
+            }
+            // files picked for an app are opened with it
+            Input::Primary(i, _) if matches!(scope, Scope::OpenWith(_)) => {
+                Some(scope.apply(results[i].clone()))
+            }
+            Input::Primary(i, action) => {
+                if let Err(e) = action.run(&config) {
+                    app.set_error(&e.to_string());
+                    continue;
+                }
+                if let Err(e) = add_recent(&results[i], &config) {
+                    log(&format!("failed to save recent results: {}", e));
+                }
+                if let Err(e) = save_last(&results[i], Some(&action), &config) {
+                    log(&format!("failed to save the last action: {}", e));
+                }
+                learn(&normalized, picked.as_ref(), &config);
+                app.exit_with(Some(&results[i]));
+                after_exit(&config.on_accept, &config);
+                break;
+            }
+            Input::RepeatLast => match last_action().map(|l| l.check().map(|_| l)) {
+                None => {
+                    app.set_error("Nothing to repeat yet");
+                    continue;
+                }
+                Some(Err(e)) => {
+                    app.set_error(&e);
+                    continue;
+                }
+                Some(Ok(LastAction {
+                    result,
+                    action: Some(action),
+                })) => {
+                    if let Err(e) = action.run(&config) {
+                        app.set_error(&e.to_string());
+                        continue;
+                    }
+                    app.exit_with(Some(&result));
+                    after_exit(&config.on_accept, &config);
+                    break;
+                }
+                Some(Ok(last)) => Some(last.result),
+            },
+            Input::ExpandGroup(i) => {
+                // the same key again collapses it
+                let (key, kind) = (scope.key(&normalized), results[i].kind());
+                expanded = match expanded {
+                    Some(e) if e == (key.clone(), kind) => None,
+                    _ => Some((key, kind)),
+                };
+                continue;
+            }
+            Input::Mode(_) | Input::NextMode if !stages.is_empty() || filter.is_some() => continue,
+            Input::Mode(i) => {
+                switch_mode(&mut app, &modes, i, &mut mode, &mut scope);
+                continue;
+            }
+            Input::NextMode => {
+                let i = (mode + 1) % modes.len().max(1);
+                switch_mode(&mut app, &modes, i, &mut mode, &mut scope);
+                continue;
+            }
+            Input::Filter => {
+                if let Some(f) = filter.take() {
+                    app.set_filtering(false);
+                    restore(&mut app, f.stage, &mut scope);
                 } else {
-                    panic!("{:?}", r);
+                    filter = Some(Filter {
+                        results: Arc::clone(&results),
+                        keys: filter_keys(&results),
+                        stage: Stage {
+                            query,
+                            prompt: app.get_prompt(),
+                            scope: scope.clone(),
+                            selection: app.get_selection(),
+                        },
+                        query: String::new(),
+                        filtered: Arc::clone(&results),
+                    });
+                    app.set_filtering(true)
+                        .set_prompt("Filter>")
+                        .set_query("")
+                        .set_selection(None);
                 }
+                continue;
+            }
+            // not while filtering, `Back` would leave filter mode instead of the new stage
+            Input::OpenWith(i) => {
+                if let (LauncherResult::App(path), true) =
+                    (&results[i], stages.len() < MAX_STAGES && filter.is_none())
+                {
+                    let name = Path::new(path).file_stem().unwrap_or_default();
+                    let prompt = format!("Open with {}: ", name.to_string_lossy());
+                    stages.push(Stage {
+                        query,
+                        prompt: app.get_prompt(),
+                        scope: scope.clone(),
+                        selection: app.get_selection(),
+                    });
+                    scope = Scope::OpenWith(path.clone());
+                    app.set_prompt(&prompt).set_query("").set_selection(None);
+                    results = Arc::new(vec![]);
+                }
+                continue;
+            }
+            Input::Back => {
+                if let Some(f) = filter.take() {
+                    app.set_filtering(false);
+                    restore(&mut app, f.stage, &mut scope);
+                } else if let Some(stage) = stages.pop() {
+                    restore(&mut app, stage, &mut scope);
+                }
+                continue;
             }
         };
-        if app.update(&results)?.wait_input(&mut index).unwrap() {
-            app.exit();
-            if let Some(i) = index {
-                select_tx.send(results[i].clone())?;
-                selection.join().unwrap();
+        if let Some(r) = &selected {
+            if let Err(e) = add_recent(r, &config) {
+                log(&format!("failed to save recent results: {}", e));
+            }
+            if let Err(e) = save_last(r, None, &config) {
+                log(&format!("failed to save the last action: {}", e));
             }
+            learn(&normalized, picked.as_ref(), &config);
+        }
+        // e.g. privileged commands only pop up an authorization dialog, so run them while the
+        // UI is still up to be able to report failures
+        if let Some(result) = selected.as_ref().and_then(|r| r.run_in_place()) {
+            if let Err(e) = result {
+                app.set_error(&e.to_string());
+                continue;
+            }
+            app.exit_with(selected.as_ref());
+            after_exit(&config.on_accept, &config);
             break;
         }
+        app.exit_with(selected.as_ref());
+        if let Some(r) = selected {
+            select_tx.send(r)?;
+            selection.join().unwrap();
+            after_exit(&config.on_accept, &config);
+        } else {
+            after_exit(&config.on_cancel, &config);
+        }
+        break;
+    }
+    // the terminal is restored and what was launched has started
+    if app.pipe_closed() {
+        process::exit(config.broken_pipe_code);
     }
     return Ok::<(), Box<dyn Error>>(());
 }
+
+// The query stays and runs again in the new scope
+fn switch_mode(
+    app: &mut App,
+    modes: &[(String, Scope)],
+    i: usize,
+    mode: &mut usize,
+    scope: &mut Scope,
+) {
+    if let Some((name, s)) = modes.get(i) {
+        *mode = i;
+        *scope = s.clone();
+        app.set_mode((*scope != Scope::All).then_some(name.as_str()))
+            .select_tab(i)
+            .set_selection(None);
+    }
+}
+
+fn restore(app: &mut App, stage: Stage, scope: &mut Scope) {
+    *scope = stage.scope;
+    app.set_prompt(&stage.prompt)
+        .set_query(&stage.query)
+        .set_selection(stage.selection);
+}
+
+// `on_accept` or `on_cancel`, once the launcher has exited. Not with `exit_print`, the
+// terminal belongs to whatever reads the result. Accepting while staying open never gets here
+fn after_exit(hook: &str, config: &Config) {
+    if !config.exit_print {
+        run_on_accept(hook);
+    }
+}
+
+// For `learn_selections`, unless the query or the result looks private
+fn learn(query: &str, picked: Option<&LauncherResult>, config: &Config) {
+    if !config.learn_selections || is_private(query, config) {
+        return;
+    }
+    if let Some(r) = picked.filter(|r| !is_private(&r.get_string(), config)) {
+        if let Err(e) = learned::learn(query, r, config.learn_half_life_days) {
+            log(&format!("failed to save the learned selection: {}", e));
+        }
+    }
+}
+
+fn learned_command(args: &[String], config: &Config) -> Result<(), Box<dyn Error>> {
+    match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        ["list"] => {
+            for (s, weight) in learned::selections(config.learn_half_life_days) {
+                println!("{:.2}\t{}\t{}", weight, s.query, s.result);
+            }
+        }
+        ["reset"] => println!("forgot {} selections", learned::reset()?),
+        _ => return Err("usage: launcher learned list | launcher learned reset".into()),
+    }
+    Ok(())
+}
+
+fn state_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let store = state::store();
+    match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        ["dir"] => println!("{}", store.dir.display()),
+        ["reset"] => match store.reset()?[..] {
+            [] => println!("nothing to forget"),
+            ref removed => println!("forgot {}", removed.join(", ")),
+        },
+        _ => return Err("usage: launcher state dir | launcher state reset".into()),
+    }
+    Ok(())
+}
+
+fn ignore_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        ["list"] => {
+            for entry in ignored() {
+                println!("{}", entry);
+            }
+        }
+        ["remove", pattern] => {
+            let removed = unignore(pattern)?;
+            if removed.is_empty() {
+                return Err(format!("nothing in the ignore list contains `{}`", pattern).into());
+            }
+            for entry in removed {
+                println!("removed {}", entry);
+            }
+        }
+        _ => return Err("usage: launcher ignore list | launcher ignore remove <pattern>".into()),
+    }
+    Ok(())
+}
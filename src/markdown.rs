@@ -0,0 +1,281 @@
+// Markdown to styled lines for the preview pane
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+use unicode_width::UnicodeWidthStr;
+
+const TAB_WIDTH: usize = 4;
+
+struct Renderer {
+    width: usize,
+    lines: Vec<Spans<'static>>,
+    line: Vec<Span<'static>>,
+    col: usize,
+    started: bool,           // prefix of the current line is written
+    styles: Vec<Style>,      // innermost last
+    indents: Vec<String>,    // continuation prefix of each nested quote and list item
+    marker: Option<String>,  // bullet for the first line of a list item
+    lists: Vec<Option<u64>>, // next number of each ordered list
+    links: Vec<String>,
+    code: bool,
+    cells: usize, // cells so far in the table row
+}
+
+impl Renderer {
+    fn style(&self) -> Style {
+        self.styles
+            .iter()
+            .fold(Style::default(), |style, s| style.patch(*s))
+    }
+
+    fn start_line(&mut self) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        let prefix = match self.marker.take() {
+            Some(marker) => self.indents[..self.indents.len() - 1].concat() + &marker,
+            None => self.indents.concat(),
+        };
+        self.col = prefix.width();
+        if !prefix.is_empty() {
+            self.line
+                .push(Span::styled(prefix, Style::default().fg(Color::DarkGray)));
+        }
+    }
+
+    fn newline(&mut self) {
+        if self.started {
+            self.lines.push(Spans::from(std::mem::take(&mut self.line)));
+            self.started = false;
+            self.col = 0;
+        }
+    }
+
+    // Ends the block with an empty line, unless there is one already
+    fn blank(&mut self) {
+        self.newline();
+        if self.lines.last().is_some_and(|l| l.width() > 0) {
+            self.lines.push(Spans::default());
+        }
+    }
+
+    fn push(&mut self, text: &str, style: Style) {
+        self.start_line();
+        self.col += text.width();
+        self.line.push(Span::styled(text.to_string(), style));
+    }
+
+    // Wraps at spaces to the width
+    fn text(&mut self, text: &str) {
+        let style = self.style();
+        let prefix = self.indents.concat().width();
+        for word in text.split_inclusive(' ') {
+            self.start_line();
+            if self.col + word.trim_end().width() > self.width && self.col > prefix {
+                self.newline();
+                self.start_line();
+            }
+            // no spaces at the start of a wrapped line
+            if self.col == prefix && word.trim().is_empty() {
+                continue;
+            }
+            self.push(word, style);
+        }
+    }
+
+    fn code_block(&mut self, text: &str) {
+        let style = Style::default().fg(Color::Yellow);
+        for line in text.lines() {
+            self.start_line();
+            self.push(&line.replace('\t', &" ".repeat(TAB_WIDTH)), style);
+            self.newline();
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading(level, ..) => {
+                self.newline();
+                let style = Style::default().add_modifier(Modifier::BOLD);
+                self.styles.push(match level {
+                    HeadingLevel::H1 => style.fg(Color::Cyan).add_modifier(Modifier::UNDERLINED),
+                    HeadingLevel::H2 => style.fg(Color::Cyan),
+                    _ => style.fg(Color::Blue),
+                });
+            }
+            Tag::BlockQuote => {
+                self.newline();
+                self.indents.push("│ ".to_string());
+            }
+            Tag::CodeBlock(kind) => {
+                self.newline();
+                if let CodeBlockKind::Fenced(lang) = kind {
+                    if !lang.is_empty() {
+                        self.push(&lang, Style::default().fg(Color::DarkGray));
+                        self.newline();
+                    }
+                }
+                self.code = true;
+            }
+            Tag::List(start) => {
+                self.newline();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.newline();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.indents.push(" ".repeat(marker.width()));
+                self.marker = Some(marker);
+            }
+            Tag::Emphasis => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link(_, url, _) => {
+                self.styles.push(
+                    Style::default()
+                        .fg(Color::Blue)
+                        .add_modifier(Modifier::UNDERLINED),
+                );
+                self.links.push(url.to_string());
+            }
+            // only the alt text
+            Tag::Image(..) => {
+                self.styles
+                    .push(Style::default().add_modifier(Modifier::ITALIC));
+                self.text("[image: ");
+            }
+            Tag::TableHead => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::BOLD)),
+            Tag::TableRow => self.cells = 0,
+            Tag::TableCell => {
+                if self.cells > 0 {
+                    self.push(" │ ", Style::default().fg(Color::DarkGray));
+                }
+                self.cells += 1;
+            }
+            Tag::Paragraph | Tag::Table(_) | Tag::FootnoteDefinition(_) => {}
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading(..) => {
+                self.styles.pop();
+                self.blank();
+            }
+            Tag::BlockQuote => {
+                self.newline();
+                self.indents.pop();
+                self.blank();
+            }
+            Tag::CodeBlock(_) => {
+                self.code = false;
+                self.blank();
+            }
+            Tag::List(_) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+            }
+            Tag::Item => {
+                self.newline();
+                self.indents.pop();
+                self.marker = None;
+            }
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough => {
+                self.styles.pop();
+            }
+            Tag::Link(..) => {
+                self.styles.pop();
+                if let Some(url) = self.links.pop().filter(|u| !u.is_empty()) {
+                    self.styles.push(Style::default().fg(Color::DarkGray));
+                    self.text(&format!(" ({})", url));
+                    self.styles.pop();
+                }
+            }
+            Tag::Image(..) => {
+                self.text("]");
+                self.styles.pop();
+            }
+            Tag::TableHead => {
+                self.styles.pop();
+                self.newline();
+            }
+            Tag::TableRow => self.newline(),
+            Tag::Paragraph | Tag::Table(_) => self.blank(),
+            Tag::TableCell | Tag::FootnoteDefinition(_) => {}
+        }
+    }
+}
+
+// Lines of at most `width` columns, except for code and long words
+pub fn render(source: &str, width: usize) -> Vec<Spans<'static>> {
+    let mut renderer = Renderer {
+        width,
+        lines: vec![],
+        line: vec![],
+        col: 0,
+        started: false,
+        styles: vec![],
+        indents: vec![],
+        marker: None,
+        lists: vec![],
+        links: vec![],
+        code: false,
+        cells: 0,
+    };
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    for event in Parser::new_ext(source, options) {
+        match event {
+            Event::Start(tag) => renderer.start(tag),
+            Event::End(tag) => renderer.end(tag),
+            Event::Text(text) if renderer.code => renderer.code_block(&text),
+            Event::Text(text) => renderer.text(&text),
+            Event::Code(code) => {
+                let style = renderer.style().fg(Color::Yellow);
+                renderer.push(&code, style);
+            }
+            Event::Html(html) => {
+                for line in html.lines() {
+                    renderer.push(line, Style::default().fg(Color::DarkGray));
+                    renderer.newline();
+                }
+            }
+            Event::SoftBreak => renderer.text(" "),
+            Event::HardBreak => renderer.newline(),
+            Event::Rule => {
+                renderer.newline();
+                let rule = "─".repeat(width.saturating_sub(renderer.indents.concat().width()));
+                renderer.push(&rule, Style::default().fg(Color::DarkGray));
+                renderer.blank();
+            }
+            Event::TaskListMarker(done) => {
+                renderer.push(if done { "[x] " } else { "[ ] " }, Style::default());
+            }
+            Event::FootnoteReference(name) => renderer.text(&format!("[{}]", name)),
+        }
+    }
+    renderer.blank();
+    if renderer.lines.last().is_some_and(|l| l.width() == 0) {
+        renderer.lines.pop();
+    }
+    renderer.lines
+}
@@ -0,0 +1,181 @@
+// Menu bar items of the app in front of the launcher's terminal.
+// System Events does the Accessibility calls, so no FFI is needed
+use crate::backend::{
+    applescript_output, applescript_string, log, set_source_error, LauncherResult, Matcher,
+};
+use fuzzy_matcher::FuzzyMatcher;
+use std::{process::Command, sync::Mutex};
+
+const ACCESSIBILITY_SETTINGS: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility";
+
+lazy_static! {
+    // enumerating takes a while and the app stays the same while the launcher is up
+    static ref MENU: Mutex<Option<Menu>> = Mutex::new(None);
+}
+
+struct Menu {
+    pid: u32,
+    app: String,
+    items: Vec<Vec<String>>, // e.g. ["File", "Export…"]
+}
+
+// Pid of the app that was in front before the terminal running the launcher
+pub fn previous_app() -> Option<u32> {
+    let output = Command::new("lsappinfo")
+        .arg("visibleProcessList")
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    // `ASN:0x0-0x2c02c-"Terminal": ASN:0x0-0x1001-"Safari": ...`, front to back
+    let asn = output
+        .split("ASN:")
+        .skip(1)
+        .map(|s| {
+            s.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches('-')
+        })
+        .nth(1)?;
+    let output = Command::new("lsappinfo")
+        .args(["info", "-only", "pid"])
+        .arg(format!("ASN:{}", asn))
+        .output()
+        .ok()?;
+    // `"pid"=1234`
+    String::from_utf8_lossy(&output.stdout)
+        .split('=')
+        .nth(1)?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn process(pid: u32) -> String {
+    format!("first process whose unix id is {}", pid)
+}
+
+// Enabled items up to one submenu deep
+fn enumerate(pid: u32) -> Result<Menu, String> {
+    let script = format!(
+        r#"tell application "System Events"
+    set p to {}
+    set out to name of p & linefeed
+    repeat with m in menu bar items of menu bar 1 of p
+        set mn to name of m
+        try
+            repeat with i in menu items of menu 1 of m
+                set n to name of i
+                if n is not missing value then
+                    if enabled of i then set out to out & mn & tab & n & linefeed
+                    try
+                        repeat with j in menu items of menu 1 of i
+                            set jn to name of j
+                            if jn is not missing value and enabled of j then set out to out & mn & tab & n & tab & jn & linefeed
+                        end repeat
+                    end try
+                end if
+            end repeat
+        end try
+    end repeat
+    return out
+end tell"#,
+        process(pid)
+    );
+    let output = applescript_output(&script).map_err(|e| e.to_string())?;
+    let mut lines = output.lines();
+    let app = lines.next().unwrap_or_default().to_string();
+    let items = lines
+        .map(|l| l.split('\t').map(|s| s.to_string()).collect())
+        .collect();
+    Ok(Menu { pid, app, items })
+}
+
+// Brings the app to the front and clicks the item
+fn click_script(pid: u32, item: &[String]) -> String {
+    let mut reference = format!(
+        "menu bar item {} of menu bar 1 of p",
+        applescript_string(&item[0])
+    );
+    for name in &item[1..] {
+        reference = format!(
+            "menu item {} of menu 1 of {}",
+            applescript_string(name),
+            reference
+        );
+    }
+    format!(
+        "tell application \"System Events\"\nset p to {}\nset frontmost of p to true\nclick {}\nend tell",
+        process(pid),
+        reference
+    )
+}
+
+// Items whose path matches `query`, best first, with their scores
+pub fn search(query: &str, len: usize, matcher: &Matcher) -> Vec<(i64, LauncherResult)> {
+    let mut menu = MENU.lock().unwrap();
+    if menu.is_none() {
+        let pid = match previous_app() {
+            Some(pid) => pid,
+            None => {
+                log("no app to search the menu of");
+                set_source_error("menu", Some("no app to search the menu of".to_string()));
+                return vec![];
+            }
+        };
+        match enumerate(pid) {
+            Ok(m) => {
+                set_source_error("menu", None);
+                *menu = Some(m)
+            }
+            Err(e) if e.contains("Accessibility") => {
+                set_source_error("menu", Some("no Accessibility access".to_string()));
+                // not cached, so the menu shows up once access is allowed
+                return vec![(
+                    0,
+                    LauncherResult::Script(
+                        "Menu search needs Accessibility access: open System Settings".to_string(),
+                        format!(
+                            "open location {}",
+                            applescript_string(ACCESSIBILITY_SETTINGS)
+                        ),
+                    ),
+                )];
+            }
+            Err(e) => {
+                log(&format!("failed to read the menu bar: {}", e));
+                set_source_error("menu", Some(e));
+                return vec![];
+            }
+        }
+    }
+    let menu = menu.as_ref().unwrap();
+    let skim = matcher.skim();
+    let mut matches = menu
+        .items
+        .iter()
+        .filter_map(|item| {
+            let path = item.join(" > ");
+            let score = if query.is_empty() {
+                0
+            } else {
+                skim.fuzzy_match(&path, query)?
+            };
+            Some((score, path, item))
+        })
+        .collect::<Vec<(i64, String, &Vec<String>)>>();
+    // stable, so equal scores keep the menu order
+    matches.sort_by_key(|m| -m.0);
+    matches
+        .into_iter()
+        .take(len)
+        .map(|(score, path, item)| {
+            let result = LauncherResult::Script(
+                format!("{}: {}", menu.app, path),
+                click_script(menu.pid, item),
+            );
+            (score, result)
+        })
+        .collect()
+}
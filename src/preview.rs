@@ -0,0 +1,499 @@
+use crate::backend::{log, Config, HOME_PATH};
+use crate::markdown;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env, fs,
+    hash::{Hash, Hasher},
+    io::Read,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::SystemTime,
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{self, ThemeSet},
+    parsing::SyntaxSet,
+};
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+};
+
+lazy_static! {
+    pub static ref THUMBNAIL_PATH: String = HOME_PATH.to_string() + "/.cache/launcher/thumbnails";
+    static ref SYNTAXES: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEMES: ThemeSet = ThemeSet::load_defaults();
+}
+
+// Text previews show this many lines, read from at most this many bytes
+const TEXT_LINES: usize = 100;
+const TEXT_BYTES: u64 = 64 * 1024;
+const TAB_WIDTH: usize = 4;
+
+// Highlighted snippets kept, the cache starts over when full
+const MAX_SNIPPETS: usize = 100;
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown", "mdown", "mkd"];
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "heic", "tif", "tiff", "bmp", "webp",
+];
+
+// Inline image protocols of the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Graphics {
+    Iterm,
+    Kitty,
+}
+
+pub fn detect_graphics() -> Option<Graphics> {
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") {
+        Some(Graphics::Kitty)
+    } else if program == "iTerm.app" || program == "WezTerm" {
+        Some(Graphics::Iterm)
+    } else {
+        None
+    }
+}
+
+// What the preview pane shows for a file
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub lines: Vec<Spans<'static>>,
+    pub thumbnail: Option<PathBuf>, // png to draw over the pane, if the terminal can
+}
+
+impl Preview {
+    fn loading() -> Preview {
+        Preview {
+            lines: vec![Spans::from("Loading...")],
+            thumbnail: None,
+        }
+    }
+}
+
+fn has_extension(path: &str, extensions: &[&str]) -> bool {
+    Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| extensions.contains(&e.as_str()))
+}
+
+// Path, modification time and width of the pane
+type Key = (String, Option<SystemTime>, u16);
+
+fn cache_key(path: &str, width: u16) -> Key {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    (path.to_string(), modified, width)
+}
+
+// How the worker renders previews
+#[derive(Clone, Copy)]
+struct Options {
+    thumbnail: bool,
+    light: bool,
+    markdown: bool,
+}
+
+// Previews are generated by a background thread, so scrolling never waits for `sips`
+pub struct Previews {
+    previews: Arc<Mutex<HashMap<Key, Arc<Preview>>>>,
+    tx: mpsc::Sender<Key>,
+    pub graphics: Option<Graphics>,
+    snippets: Mutex<HashMap<(String, &'static str), Arc<Preview>>>, // code and language
+    light: bool,
+}
+
+impl Previews {
+    pub fn new(graphics: Option<Graphics>, config: &Config) -> Previews {
+        let options = Options {
+            thumbnail: graphics.is_some(),
+            light: config.appearance == "light",
+            markdown: config.markdown_preview,
+        };
+        let previews = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, rx) = mpsc::channel::<Key>();
+        let worker_previews = Arc::clone(&previews);
+        thread::spawn(move || {
+            while let Ok(key) = rx.recv() {
+                let preview = generate(&key, options);
+                worker_previews
+                    .lock()
+                    .unwrap()
+                    .insert(key, Arc::new(preview));
+            }
+        });
+        Previews {
+            previews,
+            tx,
+            graphics,
+            snippets: Mutex::new(HashMap::new()),
+            light: options.light,
+        }
+    }
+
+    // Highlighted code of a result, see `LauncherResult::snippet`. Short enough to not need
+    // the worker
+    pub fn snippet(&self, code: &str, language: &'static str) -> Arc<Preview> {
+        let key = (code.to_string(), language);
+        let mut snippets = self.snippets.lock().unwrap();
+        if let Some(p) = snippets.get(&key) {
+            return Arc::clone(p);
+        }
+        if snippets.len() >= MAX_SNIPPETS {
+            snippets.clear();
+        }
+        let preview = Arc::new(Preview {
+            lines: highlight(Some(language), code, self.light),
+            thumbnail: None,
+        });
+        snippets.insert(key, Arc::clone(&preview));
+        preview
+    }
+
+    // Preview of the file for a pane `width` columns wide, or a placeholder while it is generated
+    pub fn get(&self, path: &str, width: u16) -> Arc<Preview> {
+        let key = cache_key(path, width);
+        let mut previews = self.previews.lock().unwrap();
+        if let Some(p) = previews.get(&key) {
+            return Arc::clone(p);
+        }
+        let loading = Arc::new(Preview::loading());
+        previews.insert(key.clone(), Arc::clone(&loading));
+        let _ = self.tx.send(key);
+        loading
+    }
+}
+
+// Spotlight attributes shown by `Details`, with their labels
+const MDLS_ATTRIBUTES: &[(&str, &str)] = &[
+    ("kMDItemKind", "Kind"),
+    ("kMDItemContentCreationDate", "Created"),
+    ("kMDItemContentModificationDate", "Modified"),
+];
+
+// Path and modification time
+type DetailsKey = (String, Option<SystemTime>);
+
+// Extended metadata of files from `mdls`, fetched by a background thread like previews
+pub struct Details {
+    details: Arc<Mutex<HashMap<DetailsKey, Arc<Vec<String>>>>>,
+    tx: mpsc::Sender<DetailsKey>,
+}
+
+impl Default for Details {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Details {
+    pub fn new() -> Details {
+        let details = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, rx) = mpsc::channel::<DetailsKey>();
+        let worker_details = Arc::clone(&details);
+        thread::spawn(move || {
+            while let Ok(key) = rx.recv() {
+                let lines = mdls(&key.0);
+                worker_details.lock().unwrap().insert(key, Arc::new(lines));
+            }
+        });
+        Details { details, tx }
+    }
+
+    // Lines about the file, or a placeholder while `mdls` runs
+    pub fn get(&self, path: &str) -> Arc<Vec<String>> {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let key = (path.to_string(), modified);
+        let mut details = self.details.lock().unwrap();
+        if let Some(d) = details.get(&key) {
+            return Arc::clone(d);
+        }
+        let loading = Arc::new(vec!["Loading...".to_string()]);
+        details.insert(key.clone(), Arc::clone(&loading));
+        let _ = self.tx.send(key);
+        loading
+    }
+}
+
+fn mdls(path: &str) -> Vec<String> {
+    let mut command = Command::new("mdls");
+    for (attribute, _) in MDLS_ATTRIBUTES {
+        command.args(["-name", attribute]);
+    }
+    command.args(["-name", "kMDItemPixelWidth", "-name", "kMDItemPixelHeight"]);
+    let output = match command.arg(path).output() {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
+        Ok(o) => return vec![String::from_utf8_lossy(&o.stderr).trim().to_string()],
+        Err(e) => return vec![format!("mdls: {}", e)],
+    };
+    // `kMDItemKind = "PNG image"`, `(null)` when the file doesn't have it
+    let value = |name: &str| {
+        output.lines().find_map(|l| {
+            let (key, value) = l.split_once('=')?;
+            let value = value.trim().trim_matches('"');
+            (key.trim() == name && value != "(null)").then(|| value.to_string())
+        })
+    };
+    let mut lines = MDLS_ATTRIBUTES
+        .iter()
+        .filter_map(|(attribute, label)| Some(format!("{}: {}", label, value(attribute)?)))
+        .collect::<Vec<String>>();
+    if let (Some(w), Some(h)) = (value("kMDItemPixelWidth"), value("kMDItemPixelHeight")) {
+        lines.push(format!("Dimensions: {} x {}", w, h));
+    }
+    if lines.is_empty() {
+        lines.push("No metadata".to_string());
+    }
+    lines
+}
+
+fn generate((path, modified, width): &Key, options: Options) -> Preview {
+    let error = |e: &dyn std::fmt::Display| Preview {
+        lines: vec![Spans::from(Span::styled(
+            e.to_string(),
+            Style::default().fg(Color::Red),
+        ))],
+        thumbnail: None,
+    };
+    let metadata = match fs::metadata(path) {
+        Ok(m) => m,
+        Err(e) => return error(&e),
+    };
+    if metadata.is_dir() {
+        return Preview {
+            lines: vec![Spans::from("directory")],
+            thumbnail: None,
+        };
+    }
+    let size = format!("{} bytes", metadata.len());
+    if has_extension(path, IMAGE_EXTENSIONS) {
+        let mut lines = vec![Spans::from(size)];
+        lines.extend(image_info(path).into_iter().map(Spans::from));
+        return Preview {
+            lines,
+            thumbnail: if options.thumbnail {
+                make_thumbnail(path, *modified, *width)
+            } else {
+                None
+            },
+        };
+    }
+    // only the beginning of big files
+    let mut head = vec![];
+    if let Err(e) = fs::File::open(path).and_then(|f| f.take(TEXT_BYTES).read_to_end(&mut head)) {
+        return error(&e);
+    }
+    if head.contains(&0) {
+        return Preview {
+            lines: vec![Spans::from(format!("binary file ({})", size))],
+            thumbnail: None,
+        };
+    }
+    let text = String::from_utf8_lossy(&head);
+    let lines = if options.markdown && has_extension(path, MARKDOWN_EXTENSIONS) {
+        markdown::render(&text, *width as usize)
+    } else {
+        let extension = Path::new(path).extension().map(|e| e.to_string_lossy());
+        highlight(extension.as_deref(), &text, options.light)
+    };
+    Preview {
+        lines,
+        thumbnail: None,
+    }
+}
+
+// `extension` names the language, e.g. 'rs', else it is guessed from the first line
+pub fn highlight(extension: Option<&str>, text: &str, light: bool) -> Vec<Spans<'static>> {
+    let syntax = extension
+        .and_then(|e| SYNTAXES.find_syntax_by_extension(e))
+        .or_else(|| SYNTAXES.find_syntax_by_first_line(text.lines().next().unwrap_or("")))
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text());
+    let theme = if light {
+        &THEMES.themes["InspiredGitHub"]
+    } else {
+        &THEMES.themes["base16-ocean.dark"]
+    };
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    text.split_inclusive('\n')
+        .take(TEXT_LINES)
+        .map(|line| {
+            let line = line.replace('\t', &" ".repeat(TAB_WIDTH));
+            let ranges = match highlighter.highlight_line(&line, &SYNTAXES) {
+                Ok(r) => r,
+                Err(_) => return Spans::from(line.trim_end().to_string()),
+            };
+            Spans::from(
+                ranges
+                    .into_iter()
+                    .map(|(style, s)| {
+                        Span::styled(s.trim_end_matches('\n').to_string(), tui_style(style))
+                    })
+                    .collect::<Vec<Span>>(),
+            )
+        })
+        .collect()
+}
+
+fn tui_style(style: highlighting::Style) -> Style {
+    let c = style.foreground;
+    Style::default().fg(Color::Rgb(c.r, c.g, c.b))
+}
+
+// Dimensions and EXIF date
+fn image_info(path: &str) -> Vec<String> {
+    let output = match Command::new("sips")
+        .args(["-g", "pixelWidth", "-g", "pixelHeight", "-g", "creation"])
+        .arg(path)
+        .output()
+    {
+        Ok(o) => String::from_utf8_lossy(&o.stdout).to_string(),
+        Err(_) => return vec![],
+    };
+    let property = |name: &str| {
+        output
+            .lines()
+            .find_map(|l| l.trim().strip_prefix(name)?.strip_prefix(':'))
+            .map(|v| v.trim().to_string())
+    };
+    let mut info = vec![];
+    if let (Some(w), Some(h)) = (property("pixelWidth"), property("pixelHeight")) {
+        info.push(format!("{} x {}", w, h));
+    }
+    if let Some(date) = property("creation") {
+        info.push(format!("taken {}", date));
+    }
+    info
+}
+
+// Thumbnails are this big when the terminal doesn't tell its size in pixels
+const THUMBNAIL_PIXELS: u32 = 512;
+
+// The window size from the TIOCGWINSZ ioctl, with the pixels some terminals fill in
+mod ffi {
+    use std::ffi::{c_int, c_ulong};
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct WinSize {
+        pub rows: u16,
+        pub columns: u16,
+        pub width: u16, // pixels, 0 if unknown
+        pub height: u16,
+    }
+
+    #[cfg(target_os = "macos")]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+    #[cfg(not(target_os = "macos"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    // Of the terminal on stdout, where the UI is drawn
+    pub fn window_size() -> Option<WinSize> {
+        let mut size = WinSize::default();
+        // SAFETY: TIOCGWINSZ writes a `struct winsize`, which `WinSize` is laid out as
+        let status = unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut WinSize) };
+        (status == 0).then_some(size)
+    }
+}
+
+// Pixels of the longer side of a thumbnail filling a pane `columns` wide and as high as the
+// terminal, `THUMBNAIL_PIXELS` if the terminal doesn't tell
+fn thumbnail_pixels(columns: u16) -> u32 {
+    match ffi::window_size() {
+        Some(size) if size.width > 0 && size.columns > 0 => {
+            let cell = size.width as u32 / size.columns as u32;
+            (cell * columns as u32).max(size.height as u32).max(1)
+        }
+        _ => THUMBNAIL_PIXELS,
+    }
+}
+
+// Png as big as the pane, cached by path, modification time and size
+fn make_thumbnail(path: &str, modified: Option<SystemTime>, columns: u16) -> Option<PathBuf> {
+    let pixels = thumbnail_pixels(columns);
+    let mut hasher = DefaultHasher::new();
+    (path, modified, pixels).hash(&mut hasher);
+    let thumbnail = Path::new(THUMBNAIL_PATH.as_str()).join(format!("{:x}.png", hasher.finish()));
+    if thumbnail.exists() {
+        return Some(thumbnail);
+    }
+    fs::create_dir_all(THUMBNAIL_PATH.as_str()).ok()?;
+    let status = Command::new("sips")
+        .args(["-s", "format", "png", "-Z", &pixels.to_string()])
+        .arg(path)
+        .arg("--out")
+        .arg(&thumbnail)
+        .output();
+    match status {
+        Ok(o) if o.status.success() => Some(thumbnail),
+        _ => {
+            log(&format!("failed to make a thumbnail of `{}`", path));
+            None
+        }
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(CHARS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Escape sequence drawing `png` at the cursor, scaled into `cols` x `rows` cells
+pub fn image_escape(graphics: Graphics, png: &Path, cols: u16, rows: u16) -> Option<String> {
+    let data = base64(&fs::read(png).ok()?);
+    Some(match graphics {
+        Graphics::Iterm => format!(
+            "\x1b]1337;File=inline=1;width={};height={};preserveAspectRatio=1:{}\x07",
+            cols, rows, data
+        ),
+        Graphics::Kitty => {
+            // chunked, `q=2` keeps the terminal from answering on stdin
+            let chunks = data.as_bytes().chunks(4096).collect::<Vec<&[u8]>>();
+            let mut escape = String::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = (i + 1 < chunks.len()) as u8;
+                let chunk = String::from_utf8_lossy(chunk);
+                if i == 0 {
+                    escape += &format!(
+                        "\x1b_Ga=T,f=100,q=2,c={},r={},m={};{}\x1b\\",
+                        cols, rows, more, chunk
+                    );
+                } else {
+                    escape += &format!("\x1b_Gm={};{}\x1b\\", more, chunk);
+                }
+            }
+            escape
+        }
+    })
+}
+
+// Removes the images drawn by `image_escape`. iTerm images go away when overwritten
+pub fn clear_images_escape(graphics: Graphics) -> &'static str {
+    match graphics {
+        Graphics::Kitty => "\x1b_Ga=d,q=2\x1b\\",
+        Graphics::Iterm => "",
+    }
+}
@@ -0,0 +1,152 @@
+// What the launcher remembers between sessions, e.g. recent and learned selections. Each store
+// is a file named after it in `Config::state_dir`, in `Config::state_format`
+use crate::backend::{expand_path, log, HOME_PATH};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// Every store, for `reset`
+pub const STORES: &[&str] = &["recent", "last", "learned"];
+
+lazy_static! {
+    static ref STATE: Mutex<StateStore> = Mutex::new(StateStore::default());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    // 'toml' or 'json'
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Toml => "toml",
+            Format::Json => "json",
+        }
+    }
+
+    fn other(self) -> Format {
+        match self {
+            Format::Toml => Format::Json,
+            Format::Json => Format::Toml,
+        }
+    }
+
+    fn parse<T: DeserializeOwned>(self, text: &str) -> Option<T> {
+        match self {
+            Format::Toml => toml::from_str(text).ok(),
+            Format::Json => serde_json::from_str(text).ok(),
+        }
+    }
+
+    fn print<T: Serialize>(self, value: &T) -> Result<String, Box<dyn Error>> {
+        Ok(match self {
+            Format::Toml => toml::to_string(value)?,
+            Format::Json => serde_json::to_string_pretty(value)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateStore {
+    pub dir: PathBuf,
+    pub format: Format,
+}
+
+impl Default for StateStore {
+    fn default() -> Self {
+        StateStore {
+            dir: PathBuf::from(HOME_PATH.to_string() + "/.cache/launcher"),
+            format: Format::Json,
+        }
+    }
+}
+
+impl StateStore {
+    pub fn new(dir: &str, format: &str) -> StateStore {
+        let format = Format::from_name(format).unwrap_or_else(|| {
+            log(&format!("unknown state_format `{}`, using json", format));
+            Format::Json
+        });
+        StateStore {
+            dir: PathBuf::from(expand_path(dir)),
+            format,
+        }
+    }
+
+    fn path(&self, name: &str, format: Format) -> PathBuf {
+        self.dir.join(format!("{}.{}", name, format.extension()))
+    }
+
+    // The store, `None` if there is none yet. One saved in the other format is read too, so
+    // changing `state_format` keeps what was remembered
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> Option<T> {
+        [self.format, self.format.other()]
+            .into_iter()
+            .find_map(|f| f.parse(&fs::read_to_string(self.path(name, f)).ok()?))
+    }
+
+    // Written next to the store and renamed over it, so a crash leaves the old one whole
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(name, self.format);
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, self.format.print(value)?)?;
+        fs::rename(&temporary, &path)?;
+        remove(&self.path(name, self.format.other()))?;
+        Ok(())
+    }
+
+    // Forgets the store, in either format. Whether there was one
+    pub fn remove(&self, name: &str) -> io::Result<bool> {
+        let mut removed = false;
+        for format in [Format::Toml, Format::Json] {
+            removed |= remove(&self.path(name, format))?;
+        }
+        Ok(removed)
+    }
+
+    // Forgets every store, returns those there were
+    pub fn reset(&self) -> io::Result<Vec<&'static str>> {
+        let mut removed = vec![];
+        for name in STORES {
+            if self.remove(name)? {
+                removed.push(*name);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+fn remove(path: &Path) -> io::Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Where and how the stores are kept from now on, from `Config::state_dir` and
+// `Config::state_format`
+pub fn configure(store: StateStore) {
+    *STATE.lock().unwrap() = store;
+}
+
+// The store in use
+pub fn store() -> StateStore {
+    STATE.lock().unwrap().clone()
+}
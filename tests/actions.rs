@@ -0,0 +1,186 @@
+use common::scratch;
+use launcher::backend::{
+    applescript_string, fill_template, privileged_script, settings_script, typing_script, Action,
+    Config, LauncherResult,
+};
+use std::collections::HashMap;
+use std::{fs, path::Path};
+
+mod common;
+
+fn rename(path: &Path) -> Action {
+    Action::Rename(path.to_string_lossy().to_string(), String::new())
+}
+
+#[test]
+fn rename_asks_for_the_file_name() {
+    let dir = scratch("rename");
+    let file = dir.join("notes.txt");
+    fs::write(&file, "").unwrap();
+    let action = rename(&file);
+    assert_eq!(
+        action.argument(),
+        Some(("Rename to>", "notes.txt".to_string()))
+    );
+    action
+        .with_argument("notes.md".to_string())
+        .run(&Config::default())
+        .unwrap();
+    assert!(!file.exists());
+    assert!(dir.join("notes.md").exists());
+}
+
+#[test]
+fn rename_refuses_paths_and_existing_files() {
+    let dir = scratch("refuse");
+    let (file, other) = (dir.join("a"), dir.join("b"));
+    fs::write(&file, "").unwrap();
+    fs::write(&other, "").unwrap();
+    assert!(rename(&file)
+        .with_argument("b".to_string())
+        .run(&Config::default())
+        .is_err());
+    assert!(rename(&file)
+        .with_argument("sub/c".to_string())
+        .run(&Config::default())
+        .is_err());
+    assert!(rename(&file)
+        .with_argument(" ".to_string())
+        .run(&Config::default())
+        .is_err());
+    assert!(file.exists());
+}
+
+#[test]
+fn move_into_a_directory() {
+    let dir = scratch("move");
+    let (file, into) = (dir.join("a"), dir.join("into"));
+    fs::write(&file, "").unwrap();
+    fs::create_dir(&into).unwrap();
+    let action = Action::Move(file.to_string_lossy().to_string(), String::new());
+    assert_eq!(
+        action.argument(),
+        Some(("Move to>", format!("{}/", dir.to_string_lossy())))
+    );
+    let missing = action
+        .clone()
+        .with_argument(dir.join("nowhere").to_string_lossy().to_string());
+    assert!(missing.run(&Config::default()).is_err());
+    action
+        .with_argument(into.to_string_lossy().to_string())
+        .run(&Config::default())
+        .unwrap();
+    assert!(into.join("a").exists());
+    assert!(!file.exists());
+}
+
+#[test]
+fn templates_are_filled_shell_quoted() {
+    let file = LauncherResult::File("/tmp/it's here/notes.txt".to_string());
+    assert_eq!(
+        fill_template("cp {} {dir}/{name}.bak", &file),
+        r"cp '/tmp/it'\''s here/notes.txt' '/tmp/it'\''s here'/'notes.txt'.bak"
+    );
+    let url = LauncherResult::Url("https://example.com".to_string());
+    assert_eq!(fill_template("open {}", &url), "open 'https://example.com'");
+    // `{dir}` is empty without a path, shell braces are left alone
+    assert_eq!(fill_template("echo {dir} ${HOME}", &url), "echo '' ${HOME}");
+    // a placeholder in a value is not filled again
+    let braces = LauncherResult::File("/tmp/{}".to_string());
+    assert_eq!(fill_template("open {name}", &braces), "open '{}'");
+}
+
+#[test]
+fn templates_run_on_enter_for_their_kind() {
+    let enter = HashMap::from([("bin".to_string(), "echo {}".to_string())]);
+    let bin = LauncherResult::Bin("/bin/ls".to_string());
+    assert_eq!(
+        bin.enter_template(&enter),
+        Some(Action::Run("echo '/bin/ls'".to_string()))
+    );
+    assert_eq!(bin.enter_label(&enter), "Run");
+    // other kinds keep their default
+    let file = LauncherResult::File("/tmp/a".to_string());
+    assert_eq!(file.enter_template(&enter), None);
+    assert!(Action::Run("true".to_string())
+        .run(&Config::default())
+        .is_ok());
+    // started without waiting, an error exit is only logged
+    assert!(Action::Run("sleep 5; false".to_string())
+        .run(&Config::default())
+        .is_ok());
+    // templates are valid `[enter]` values, other unknown names are not
+    let config = Config {
+        enter: HashMap::from([
+            ("bin".to_string(), "echo {}".to_string()),
+            ("file".to_string(), "echo".to_string()),
+        ]),
+        ..Config::default()
+    };
+    assert_eq!(config.enter_errors().len(), 1);
+}
+
+#[test]
+fn typing_presses_returns_and_waits_between_keys() {
+    let at_once = typing_script(42, "a \"b\"\nc", 0);
+    assert!(at_once.contains("unix id is 42"));
+    assert!(
+        at_once.contains("    keystroke \"a \\\"b\\\"\"\n    key code 36\n    keystroke \"c\"\n")
+    );
+    let slow = typing_script(42, "ab", 20);
+    assert!(slow.contains("    keystroke \"a\"\n    delay 0.02\n    keystroke \"b\"\nend tell"));
+}
+
+#[test]
+fn settings_offered_for_apps_with_a_menu_bar() {
+    let names = |path: &str| {
+        LauncherResult::App(path.to_string())
+            .actions(&HashMap::new())
+            .into_iter()
+            .map(|a| a.label)
+            .filter(|l| l.ends_with(" settings"))
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(names("/Applications/Safari.app"), ["Open Safari settings"]);
+    assert!(names("/System/Library/CoreServices/Dock.app").is_empty());
+    assert!(names("/System/Applications/System Settings.app").is_empty());
+    // not for files opened with an app
+    let with = LauncherResult::AppWith("/Applications/Safari.app".to_string(), "a".to_string());
+    assert!(!with
+        .actions(&HashMap::new())
+        .iter()
+        .any(|a| a.name == "open_settings"));
+}
+
+#[test]
+fn settings_clicked_in_the_app_once_in_front() {
+    let script = settings_script("/Applications/Safari.app");
+    assert!(script.starts_with("set bundle to id of application \"/Applications/Safari.app\"\n"));
+    assert!(script.contains("if frontmost of (first process whose bundle identifier is bundle)"));
+    assert!(script.contains("tell (first process whose bundle identifier is bundle)"));
+    assert!(!script.contains("first process whose frontmost"));
+}
+
+#[test]
+fn applescript_strings_escape_quotes_and_backslashes() {
+    assert_eq!(applescript_string("plain"), "\"plain\"");
+    assert_eq!(
+        applescript_string(r#"say "hi" \ bye"#),
+        r#""say \"hi\" \\ bye""#
+    );
+    // the shell expands these, AppleScript keeps them as they are
+    assert_eq!(applescript_string("echo $HOME `id`"), "\"echo $HOME `id`\"");
+    assert_eq!(applescript_string("a\nb"), "\"a\nb\"");
+}
+
+#[test]
+fn privileged_commands_reach_the_shell_as_typed() {
+    assert_eq!(
+        privileged_script(r#"echo "$USER" > /tmp/a\ b"#),
+        r#"do shell script "echo \"$USER\" > /tmp/a\\ b" with administrator privileges"#
+    );
+    assert_eq!(
+        privileged_script("purge\nsync"),
+        "do shell script \"purge\nsync\" with administrator privileges"
+    );
+}
@@ -0,0 +1,38 @@
+use launcher::backend::{Config, LauncherResult};
+use launcher::calc;
+
+// One test, the history is shared by those of a file
+#[test]
+fn ans_is_the_last_accepted_value_in_full() {
+    let vars = || calc::vars(None);
+    assert_eq!(
+        calc::eval("ans", &vars()),
+        Some(Err("no previous result".to_string()))
+    );
+    let config = Config {
+        calc_copy_digits: Some(3),
+        ..Config::default()
+    };
+    let third = LauncherResult::Calc(
+        "1 / 3".to_string(),
+        "0.3333".to_string(),
+        calc::format(1.0 / 3.0, config.calc_copy_digits),
+    );
+    third.accepted(&config);
+    // on its own too, not the rounded copy
+    assert_eq!(calc::eval("ans", &vars()), Some(Ok(1.0 / 3.0)));
+    assert_eq!(calc::eval("ans * 3", &vars()), Some(Ok(1.0)));
+    assert_eq!(calc::history(None), [("1 / 3".to_string(), 1.0 / 3.0)]);
+
+    // a calculation accepted from the history keeps its value, whatever `ans` is now
+    calc::remember("2 / 3", 2.0 / 3.0, None, true, false);
+    let listed = LauncherResult::Calc(
+        "ans * 2".to_string(),
+        "0.6667".to_string(),
+        calc::format(2.0 / 3.0, config.calc_copy_digits),
+    );
+    calc::remember("ans * 2", 2.0 / 3.0, None, true, false);
+    calc::remember("5", 5.0, None, true, false);
+    listed.accepted(&config);
+    assert_eq!(calc::eval("ans", &vars()), Some(Ok(2.0 / 3.0)));
+}
@@ -0,0 +1,32 @@
+// Shared by the integration tests, `mod common;` in each that uses it
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+// An empty directory of its own per test, removed with what is in it once dropped
+pub struct Scratch(PathBuf);
+
+// `name` is unique among the tests of a file, the file's own process keeps them apart from
+// those of the others
+pub fn scratch(name: &str) -> Scratch {
+    let dir = env::temp_dir().join(format!("launcher-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    Scratch(dir)
+}
+
+impl Deref for Scratch {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
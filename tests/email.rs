@@ -0,0 +1,49 @@
+use launcher::backend::{mailto, parse_email};
+
+fn parsed(text: &str, pattern: &str) -> (String, String) {
+    parse_email(text, pattern)
+}
+
+fn pair(to: &str, subject: &str) -> (String, String) {
+    (to.to_string(), subject.to_string())
+}
+
+#[test]
+fn recipient_and_subject() {
+    let pattern = "{to} about {subject}";
+    assert_eq!(parsed("alice about lunch", pattern), pair("alice", "lunch"));
+    assert_eq!(
+        parsed("bob@example.com About the Q3 plan", pattern),
+        pair("bob@example.com", "the Q3 plan")
+    );
+    // no recipient
+    assert_eq!(parsed("about lunch", pattern), pair("", "lunch"));
+    assert_eq!(
+        parsed("lunch on friday", pattern),
+        pair("", "lunch on friday")
+    );
+    assert_eq!(parsed("alice", pattern), pair("alice", ""));
+    assert_eq!(parsed("", pattern), pair("", ""));
+}
+
+#[test]
+fn other_patterns() {
+    assert_eq!(
+        parsed("lunch plans to alice", "{subject} to {to}"),
+        pair("alice", "lunch plans")
+    );
+    assert_eq!(
+        parsed("alice lunch plans", "{to} {subject}"),
+        pair("alice", "lunch plans")
+    );
+}
+
+#[test]
+fn mailto_links() {
+    assert_eq!(mailto("alice", "lunch"), "mailto:alice?subject=lunch");
+    assert_eq!(
+        mailto("bob@example.com", "it's 5 & up"),
+        "mailto:bob@example.com?subject=it%27s%205%20%26%20up"
+    );
+    assert_eq!(mailto("", ""), "mailto:");
+}
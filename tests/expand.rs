@@ -0,0 +1,70 @@
+use common::scratch;
+use launcher::backend::{
+    expand_path, expand_tilde, expand_vars, new_magic_cookie, Config, LauncherResult, HOME_PATH,
+};
+use std::{env, fs, path::Path};
+
+mod common;
+
+#[test]
+fn tilde_for_homes() {
+    let home = HOME_PATH.as_str();
+    assert_eq!(expand_tilde("~"), home);
+    assert_eq!(expand_tilde("~/notes.txt"), format!("{}/notes.txt", home));
+    // another user's home is next to this one
+    let me = Path::new(home).file_name().unwrap().to_string_lossy();
+    assert_eq!(expand_tilde(&format!("~{}/a", me)), format!("{}/a", home));
+    assert_eq!(expand_tilde("~no-such-user/a"), "~no-such-user/a");
+    // only at the start
+    assert_eq!(expand_tilde("a/~/b"), "a/~/b");
+}
+
+#[test]
+fn variables_with_and_without_braces() {
+    env::set_var("LAUNCHER_TEST_DIR", "/fixture/dir");
+    assert_eq!(expand_vars("$LAUNCHER_TEST_DIR/a"), "/fixture/dir/a");
+    assert_eq!(expand_vars("${LAUNCHER_TEST_DIR}a"), "/fixture/dira");
+    // undefined ones are dropped, a lone `$` is kept
+    assert_eq!(expand_vars("a$LAUNCHER_TEST_UNSET/b"), "a/b");
+    assert_eq!(expand_vars("${LAUNCHER_TEST_UNSET}b"), "b");
+    assert_eq!(expand_vars("costs 5$"), "costs 5$");
+}
+
+#[test]
+fn escaped_dollars() {
+    env::set_var("LAUNCHER_TEST_PRICE", "5");
+    assert_eq!(
+        expand_vars("\\$LAUNCHER_TEST_PRICE"),
+        "$LAUNCHER_TEST_PRICE"
+    );
+    assert_eq!(expand_vars("\\a"), "\\a");
+}
+
+#[test]
+fn paths_expand_tilde_then_variables() {
+    env::set_var("LAUNCHER_TEST_SUB", "projects");
+    assert_eq!(
+        expand_path("~/$LAUNCHER_TEST_SUB/x"),
+        format!("{}/projects/x", HOME_PATH.as_str())
+    );
+    // a variable holding a tilde is not expanded again
+    env::set_var("LAUNCHER_TEST_TILDE", "~/x");
+    assert_eq!(expand_path("$LAUNCHER_TEST_TILDE"), "~/x");
+}
+
+#[test]
+fn commands_are_left_to_the_shell() {
+    env::set_var("LAUNCHER_TEST_NAME", "a;b");
+    let dir = scratch("expand-exec");
+    let out = dir.join("out");
+    // quotes, positional and special parameters, operators and values with metacharacters
+    let cmd = format!(
+        "{{ echo '$HOME'; echo 'x y' | awk '{{print $1}}'; echo ${{LAUNCHER_TEST_UNSET:-def}}; \
+         echo $LAUNCHER_TEST_NAME; }} > {}",
+        out.display()
+    );
+    let exec = LauncherResult::Command("exec".to_string(), cmd);
+    exec.select(&Config::default(), &new_magic_cookie().unwrap())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "$HOME\nx\ndef\na;b\n");
+}
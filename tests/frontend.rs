@@ -0,0 +1,906 @@
+// Scripted sessions against a `TestBackend`: keys go through `App::handle_event`, fixture
+// results through `App::update`, and what was drawn is compared as lines of text
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use launcher::backend::{group_results, Action, Config, LauncherResult};
+use launcher::frontend::{print_launched, App, Input};
+use std::io::{self, Write};
+use tui::{
+    backend::{Backend, TestBackend},
+    buffer::Cell,
+    layout::Rect,
+    style::Modifier,
+};
+use unicode_width::UnicodeWidthStr;
+
+// `TestBackend` and a sink for the escapes that `App` writes past tui, e.g. of images
+struct Screen(TestBackend);
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend for Screen {
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>,
+    {
+        self.0.draw(content)
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.0.hide_cursor()
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.0.show_cursor()
+    }
+
+    fn get_cursor(&mut self) -> io::Result<(u16, u16)> {
+        self.0.get_cursor()
+    }
+
+    fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
+        self.0.set_cursor(x, y)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.0.clear()
+    }
+
+    fn size(&self) -> io::Result<Rect> {
+        self.0.size()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Backend::flush(&mut self.0)
+    }
+}
+
+fn app(width: u16, height: u16) -> App<Screen> {
+    app_with(width, height, Config::default())
+}
+
+fn app_with(width: u16, height: u16, config: Config) -> App<Screen> {
+    // every change is drawn right away
+    let config = Config {
+        max_fps: 0,
+        ..config
+    };
+    App::with_backend(Screen(TestBackend::new(width, height)), "Query>", &config).unwrap()
+}
+
+fn press(app: &mut App<Screen>, code: KeyCode, modifiers: KeyModifiers) -> Input {
+    app.handle_event(Event::Key(KeyEvent::new(code, modifiers)))
+}
+
+fn key(app: &mut App<Screen>, code: KeyCode) -> Input {
+    press(app, code, KeyModifiers::NONE)
+}
+
+fn type_text(app: &mut App<Screen>, text: &str) {
+    for ch in text.chars() {
+        key(app, KeyCode::Char(ch));
+    }
+}
+
+fn bins(names: &[&str]) -> Vec<LauncherResult> {
+    names
+        .iter()
+        .map(|n| LauncherResult::Bin(format!("/fixture/bin/{}", n)))
+        .collect()
+}
+
+// The drawn lines, without trailing spaces. A wide character's second cell is skipped
+fn screen(app: &App<Screen>) -> Vec<String> {
+    let buffer = app.backend().0.buffer();
+    let width = buffer.area.width as usize;
+    buffer
+        .content
+        .chunks(width)
+        .map(|row| {
+            let mut line = String::new();
+            let mut skip = 0;
+            for cell in row {
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                skip = cell.symbol.width().saturating_sub(1);
+                line.push_str(&cell.symbol);
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+fn cursor(app: &mut App<Screen>) -> (u16, u16) {
+    app.backend_mut().get_cursor().unwrap()
+}
+
+#[test]
+fn empty_state() {
+    let mut app = app(30, 6);
+    app.update(&bins(&["ar", "as"])).unwrap();
+    // a hint instead of results for an empty query, see `Config::empty_query`
+    assert_eq!(
+        screen(&app),
+        [
+            "┌────────────────────────────┐",
+            "│Query>                      │",
+            "└────────────────────────────┘",
+            "┌────────────────────────────┐",
+            "│Type to search apps, files a│",
+            "└────────────────────────────┘",
+        ]
+    );
+    assert_eq!(app.get_selection(), None);
+    assert_eq!(cursor(&mut app), (7, 1));
+}
+
+#[test]
+fn results_with_selection() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "a");
+    app.update(&bins(&["ar", "as"])).unwrap();
+    assert_eq!(
+        screen(&app),
+        [
+            "┌────────────────────────────┐",
+            "│Query>a                ↵ Run│",
+            "└────────────────────────────┘",
+            "┌────────────────────────────┐",
+            "│>> Bin  | /fixture/bin/ar   │",
+            "│   Bin  | /fixture/bin/as   │",
+            "└────────────────────────────┘",
+        ]
+    );
+    assert_eq!(app.get_selection(), Some(0));
+}
+
+#[test]
+fn multibyte_editing() {
+    let mut app = app(30, 5);
+    type_text(&mut app, "日本");
+    key(&mut app, KeyCode::Left);
+    type_text(&mut app, "x");
+    assert_eq!(app.get_query(), "日x本");
+    app.update(&[]).unwrap();
+    // wide characters take two columns
+    assert_eq!(screen(&app)[1], "│Query>日x本                 │");
+    assert_eq!(cursor(&mut app), (10, 1));
+    key(&mut app, KeyCode::Backspace);
+    key(&mut app, KeyCode::Backspace);
+    assert_eq!(app.get_query(), "本");
+    app.update(&[]).unwrap();
+    assert_eq!(cursor(&mut app), (7, 1));
+}
+
+#[test]
+fn query_shown_as_typed() {
+    let mut app = app(30, 5);
+    // decomposed, as some input methods send it
+    type_text(&mut app, "cafe\u{301}  ");
+    assert_eq!(app.get_query(), "cafe\u{301}  ");
+    app.update(&[]).unwrap();
+    assert!(screen(&app)[1].starts_with("│Query>cafe\u{301} "));
+}
+
+#[test]
+fn delete_at_the_cursor() {
+    let mut app = app(30, 5);
+    type_text(&mut app, "é→ü");
+    key(&mut app, KeyCode::Left);
+    key(&mut app, KeyCode::Left);
+    key(&mut app, KeyCode::Delete);
+    assert_eq!(app.get_query(), "éü");
+    // nothing after the cursor at the end
+    key(&mut app, KeyCode::Right);
+    key(&mut app, KeyCode::Delete);
+    assert_eq!(app.get_query(), "éü");
+    // a second Backspace without `delete_forward`
+    let config = Config {
+        delete_forward: false,
+        ..Config::default()
+    };
+    let mut app = app_with(30, 5, config);
+    type_text(&mut app, "é→ü");
+    key(&mut app, KeyCode::Left);
+    key(&mut app, KeyCode::Delete);
+    assert_eq!(app.get_query(), "éü");
+}
+
+#[test]
+fn backspace_at_the_start() {
+    let mut app = app(30, 5);
+    type_text(&mut app, "ab");
+    key(&mut app, KeyCode::Left);
+    key(&mut app, KeyCode::Left);
+    key(&mut app, KeyCode::Left);
+    key(&mut app, KeyCode::Backspace);
+    assert_eq!(app.get_query(), "ab");
+    type_text(&mut app, "c");
+    assert_eq!(app.get_query(), "cab");
+}
+
+#[test]
+fn selection_wraps_around() {
+    let mut app = app(30, 8);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as", "awk"]);
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Up);
+    app.update(&list).unwrap();
+    assert_eq!(app.get_selection(), Some(2));
+    assert_eq!(screen(&app)[6], "│>> Bin  | /fixture/bin/awk  │");
+    key(&mut app, KeyCode::Down);
+    app.update(&list).unwrap();
+    assert_eq!(app.get_selection(), Some(0));
+}
+
+#[test]
+fn selection_clamped_when_the_list_shrinks() {
+    let mut app = app(30, 9);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as", "awk", "apropos"]);
+    app.update(&list).unwrap();
+    for _ in 0..3 {
+        key(&mut app, KeyCode::Down);
+    }
+    app.update(&list).unwrap();
+    assert_eq!(app.get_selection(), Some(3));
+    type_text(&mut app, "r");
+    app.update(&bins(&["ar", "apropos"])).unwrap();
+    assert_eq!(app.get_selection(), Some(1));
+    assert_eq!(screen(&app)[5], "│>> Bin  | /fixture/bin/aprop│");
+    // and none without results
+    app.update(&[]).unwrap();
+    assert_eq!(app.get_selection(), None);
+    assert!(matches!(key(&mut app, KeyCode::Enter), Input::Continue));
+}
+
+#[test]
+fn completion_commits_on_typing() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Tab);
+    app.update(&list).unwrap();
+    // the completion is shown in place of the query, which stays as typed
+    assert_eq!(screen(&app)[1], "│Query>/fixture/bin/as  ↵ Run│");
+    assert_eq!(app.get_query(), "a");
+    type_text(&mut app, " x");
+    assert_eq!(app.get_query(), "/fixture/bin/as x");
+}
+
+#[test]
+fn completion_cancelled_by_typing() {
+    let config = Config {
+        completion_typing: "cancel".to_string(),
+        ..Config::default()
+    };
+    let mut app = app_with(30, 7, config);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Tab);
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[1], "│Query>/fixture/bin/as  ↵ Run│");
+    type_text(&mut app, "r");
+    assert_eq!(app.get_query(), "ar");
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[1], "│Query>ar               ↵ Run│");
+}
+
+#[test]
+fn action_argument_typed_in_the_input_field() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "no");
+    let list = vec![LauncherResult::File("/fixture/notes.txt".to_string())];
+    app.update(&list).unwrap();
+    assert!(matches!(key(&mut app, KeyCode::F(2)), Input::Continue));
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[1], "│Rename to>notes.txt         │");
+    for _ in 0..3 {
+        key(&mut app, KeyCode::Backspace);
+    }
+    type_text(&mut app, "md");
+    // the query is left alone
+    assert_eq!(app.get_query(), "no");
+    match key(&mut app, KeyCode::Enter) {
+        Input::Action(action) => assert_eq!(
+            action,
+            Action::Rename("/fixture/notes.txt".to_string(), "notes.md".to_string())
+        ),
+        _ => panic!("the action did not run"),
+    }
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[1], "│Query>no              ↵ Open│");
+}
+
+#[test]
+fn action_argument_cancelled_with_esc() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "no");
+    let list = vec![LauncherResult::File("/fixture/notes.txt".to_string())];
+    app.update(&list).unwrap();
+    press(
+        &mut app,
+        KeyCode::Char('m'),
+        KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+    );
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[1], "│Move to>/fixture/           │");
+    assert!(matches!(key(&mut app, KeyCode::Esc), Input::Continue));
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[1], "│Query>no              ↵ Open│");
+    // Esc again leaves as usual, quitting at the first stage
+    assert!(matches!(key(&mut app, KeyCode::Esc), Input::Exit));
+}
+
+#[test]
+fn completion_cancelled_with_esc() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Tab);
+    app.update(&list).unwrap();
+    assert!(matches!(key(&mut app, KeyCode::Esc), Input::Continue));
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[1], "│Query>a                ↵ Run│");
+    assert!(matches!(key(&mut app, KeyCode::Esc), Input::Exit));
+}
+
+#[test]
+fn enter_selects() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Down);
+    app.update(&list).unwrap();
+    assert!(matches!(key(&mut app, KeyCode::Enter), Input::Select(1)));
+}
+
+#[test]
+fn enter_adds_to_the_query_when_building() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "cp a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    press(&mut app, KeyCode::Char('b'), KeyModifiers::CONTROL);
+    app.update(&list).unwrap();
+    assert!(screen(&app).iter().any(|l| l.contains("↵ Add")));
+    // the word being typed is replaced, and the next one can start right away
+    assert!(matches!(key(&mut app, KeyCode::Enter), Input::Continue));
+    assert_eq!(app.get_query(), "cp /fixture/bin/ar ");
+    type_text(&mut app, "a");
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Enter);
+    assert_eq!(app.get_query(), "cp /fixture/bin/ar /fixture/bin/ar ");
+    // Ctrl+L launches meanwhile
+    let input = press(&mut app, KeyCode::Char('l'), KeyModifiers::CONTROL);
+    assert!(matches!(input, Input::Select(0)));
+}
+
+#[test]
+fn action_icons_at_the_right_of_rows() {
+    let config = Config {
+        action_icons: true,
+        highlight_symbol_right: " <".to_string(),
+        ..Config::default()
+    };
+    let mut app = app_with(30, 7, config);
+    type_text(&mut app, "a");
+    let list = [
+        LauncherResult::File("/fixture/a_long_file_name.txt".to_string()),
+        LauncherResult::Bin("/fixture/bin/ar".to_string()),
+    ];
+    app.update(&list).unwrap();
+    // long results are cut before the icons, and the marker comes after them
+    assert_eq!(screen(&app)[4], "│>> File | /fixture/a_l ⧉⌫✎ <│");
+    assert_eq!(screen(&app)[5], "│   Bin  | /fixture/bin/ar  ⧉│");
+}
+
+#[test]
+fn printing_to_a_closed_pipe() {
+    let r = LauncherResult::Bin("/fixture/bin/ar".to_string());
+    let (reader, mut writer) = io::pipe().unwrap();
+    drop(reader);
+    assert!(!print_launched(&mut writer, &r).unwrap());
+    let mut out = vec![];
+    assert!(print_launched(&mut out, &r).unwrap());
+    assert_eq!(out, b"/fixture/bin/ar\n");
+}
+
+#[test]
+fn details_line_up_in_a_right_column() {
+    let config = Config {
+        detail_column: true,
+        ..Config::default()
+    };
+    let mut app = app_with(40, 8, config);
+    type_text(&mut app, "n");
+    let list = [
+        LauncherResult::File("/fixture/notes.txt".to_string()),
+        LauncherResult::File("/fixture/deep/dir/a_very_long_file_name.txt".to_string()),
+        LauncherResult::Calc("1+1".to_string(), "2".to_string(), "2".to_string()),
+    ];
+    app.update(&list).unwrap();
+    // the long name is cut to make room, details only once the names are short
+    assert_eq!(
+        &screen(&app)[4..7],
+        [
+            "│>> File | notes.txt           /fixture│",
+            "│   File | a_very_lon /fixture/deep/dir│",
+            "│   Calc | 1+1                      = 2│",
+        ]
+    );
+    // details are cut once names are down to a few characters
+    let mut app = app_with(
+        30,
+        8,
+        Config {
+            detail_column: true,
+            ..Config::default()
+        },
+    );
+    type_text(&mut app, "n");
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[5], "│   File | a_very_lo /fixture│");
+}
+
+#[test]
+fn footer_with_the_version_and_config_file() {
+    let config = Config {
+        show_footer: true,
+        loaded_from: Some("/fixture/launcher.toml".to_string()),
+        ..Config::default()
+    };
+    let mut app = app_with(40, 7, config);
+    app.update(&[]).unwrap();
+    let version = env!("CARGO_PKG_VERSION");
+    assert_eq!(
+        screen(&app)[6],
+        format!("launcher {} | /fixture/launcher.toml", version)
+    );
+    // the results make room for it
+    assert_eq!(screen(&app)[5], format!("└{}┘", "─".repeat(38)));
+}
+
+#[test]
+fn actions_menu_over_the_results() {
+    let mut app = app(50, 12);
+    type_text(&mut app, "a");
+    let list = bins(&["ar"]);
+    app.update(&list).unwrap();
+    let ctrl_k = |app: &mut App<Screen>| press(app, KeyCode::Char('k'), KeyModifiers::CONTROL);
+    ctrl_k(&mut app);
+    app.update(&list).unwrap();
+    // what Enter does first, then the key of each action
+    assert_eq!(
+        &screen(&app)[3..6],
+        [
+            "┌┌Actions──────────────────────────────────────┐─┐",
+            "││>> Run                          ↵            │ │",
+            "││   Copy full path               ctrl+shift+c │ │",
+        ]
+    );
+    // Esc goes back to the results
+    key(&mut app, KeyCode::Esc);
+    app.update(&list).unwrap();
+    assert!(!screen(&app).iter().any(|l| l.contains("Actions")));
+    // Enter runs the selected action, the first is Enter's
+    ctrl_k(&mut app);
+    assert!(matches!(key(&mut app, KeyCode::Enter), Input::Select(0)));
+    ctrl_k(&mut app);
+    key(&mut app, KeyCode::Down);
+    key(&mut app, KeyCode::Down);
+    assert!(matches!(
+        key(&mut app, KeyCode::Enter),
+        Input::Action(Action::CopyText(text)) if text == "ar"
+    ));
+}
+
+#[test]
+fn ctrl_c_exits() {
+    let mut app = app(30, 5);
+    let input = press(&mut app, KeyCode::Char('c'), KeyModifiers::CONTROL);
+    assert!(matches!(input, Input::Exit));
+    assert_eq!(app.get_query(), "");
+}
+
+#[test]
+fn ctrl_c_copies_the_selection() {
+    let config = Config {
+        quit_key: "copy".to_string(),
+        ..Config::default()
+    };
+    let mut app = app_with(30, 7, config);
+    let ctrl_c = |app: &mut App<Screen>| press(app, KeyCode::Char('c'), KeyModifiers::CONTROL);
+    assert!(matches!(ctrl_c(&mut app), Input::Continue));
+    type_text(&mut app, "a");
+    app.update(&bins(&["ar"])).unwrap();
+    assert!(matches!(
+        ctrl_c(&mut app),
+        Input::Action(Action::CopyText(text)) if text == "/fixture/bin/ar"
+    ));
+    // which quits at the first stage
+    assert!(matches!(key(&mut app, KeyCode::Esc), Input::Exit));
+    app.set_breadcrumb(Some(vec!["All".to_string(), "Filter".to_string()]));
+    assert!(matches!(key(&mut app, KeyCode::Esc), Input::Back));
+}
+
+#[test]
+fn ctrl_c_twice_to_quit() {
+    let config = Config {
+        quit_key: "double".to_string(),
+        ..Config::default()
+    };
+    let mut app = app_with(30, 7, config);
+    let ctrl_c = |app: &mut App<Screen>| press(app, KeyCode::Char('c'), KeyModifiers::CONTROL);
+    assert!(matches!(ctrl_c(&mut app), Input::Continue));
+    app.update(&[]).unwrap();
+    assert!(screen(&app)
+        .iter()
+        .any(|l| l.contains("Press again to quit")));
+    // another key in between starts over
+    type_text(&mut app, "a");
+    assert!(matches!(ctrl_c(&mut app), Input::Continue));
+    assert!(matches!(ctrl_c(&mut app), Input::Exit));
+}
+
+#[test]
+fn key_releases_are_ignored() {
+    let mut app = app(30, 5);
+    let release = KeyEvent {
+        kind: KeyEventKind::Release,
+        ..KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE)
+    };
+    assert!(matches!(
+        app.handle_event(Event::Key(release)),
+        Input::Continue
+    ));
+    assert_eq!(app.get_query(), "");
+}
+
+#[test]
+fn redrawn_after_resize() {
+    let mut app = app(30, 6);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    app.backend_mut().0.resize(20, 7);
+    assert!(matches!(
+        app.handle_event(Event::Resize(20, 7)),
+        Input::Continue
+    ));
+    app.update(&list).unwrap();
+    // cut to the narrower box
+    assert_eq!(
+        screen(&app),
+        [
+            "┌──────────────────┐",
+            "│Query>a      ↵ Run│",
+            "└──────────────────┘",
+            "┌──────────────────┐",
+            "│>> Bin  | /fixture│",
+            "│   Bin  | /fixture│",
+            "└──────────────────┘",
+        ]
+    );
+}
+
+#[test]
+fn more_indicator_counts_hidden_results() {
+    let config = Config {
+        more_indicator: true,
+        ..Config::default()
+    };
+    let mut app = app_with(30, 7, config);
+    type_text(&mut app, "a");
+    app.update(&bins(&["ar", "as", "awk", "apropos"])).unwrap();
+    // the last row makes room for the count
+    assert_eq!(screen(&app)[4], "│>> Bin  | /fixture/bin/ar   │");
+    assert_eq!(screen(&app)[5], "│+3 more                     │");
+}
+
+#[test]
+fn enter_after_tab_launches() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Tab);
+    app.update(&list).unwrap();
+    assert!(matches!(key(&mut app, KeyCode::Enter), Input::Select(1)));
+}
+
+#[test]
+fn enter_after_tab_completes() {
+    let config = Config {
+        tab_enter: "complete".to_string(),
+        ..Config::default()
+    };
+    let mut app = app_with(30, 7, config);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Tab);
+    app.update(&list).unwrap();
+    assert!(matches!(key(&mut app, KeyCode::Enter), Input::Continue));
+    assert_eq!(app.get_query(), "/fixture/bin/as");
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[1], "│Query>/fixture/bin/as  ↵ Run│");
+    // the next Enter launches
+    assert!(matches!(key(&mut app, KeyCode::Enter), Input::Select(1)));
+}
+
+#[test]
+fn completion_trailing_space_for_non_paths() {
+    let config = Config {
+        commit_trailing_space: true,
+        ..Config::default()
+    };
+    let mut app = app_with(40, 7, config);
+    type_text(&mut app, "a");
+    let list = vec![
+        LauncherResult::Url("http://a.io".to_string()),
+        LauncherResult::Url("http://a.co".to_string()),
+    ];
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Tab);
+    app.update(&list).unwrap();
+    type_text(&mut app, "x");
+    assert_eq!(app.get_query(), "http://a.co x");
+    // paths are left as they are
+    let mut app = app_with(
+        40,
+        7,
+        Config {
+            commit_trailing_space: true,
+            ..Config::default()
+        },
+    );
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    key(&mut app, KeyCode::Tab);
+    app.update(&list).unwrap();
+    type_text(&mut app, "x");
+    assert_eq!(app.get_query(), "/fixture/bin/asx");
+}
+
+#[test]
+fn huge_lists_scroll_to_their_end() {
+    let config = Config {
+        max_rendered: 10,
+        ..Config::default()
+    };
+    let mut app = app_with(32, 8, config);
+    type_text(&mut app, "b");
+    let names = (0..100_000)
+        .map(|i| format!("b{}", i))
+        .collect::<Vec<String>>();
+    let list = bins(&names.iter().map(|n| n.as_str()).collect::<Vec<&str>>());
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[4], "│>> Bin  | /fixture/bin/b0     │");
+    // every result counts, not only those drawn
+    assert_eq!(screen(&app)[6], "│+99998 more                   │");
+    // Up from the first result wraps around to the last
+    key(&mut app, KeyCode::Up);
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[4], "│   Bin  | /fixture/bin/b99998 │");
+    assert_eq!(screen(&app)[5], "│>> Bin  | /fixture/bin/b99999 │");
+    key(&mut app, KeyCode::Up);
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[4], "│>> Bin  | /fixture/bin/b99998 │");
+    // and Down back to the first
+    key(&mut app, KeyCode::Down);
+    key(&mut app, KeyCode::Down);
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[4], "│>> Bin  | /fixture/bin/b0     │");
+}
+
+#[test]
+fn breadcrumb_above_the_input_field() {
+    let mut app = app(30, 8);
+    app.set_breadcrumb(Some(vec![
+        "All".to_string(),
+        "Open with Preview".to_string(),
+    ]))
+    .set_prompt("Open with Preview: ");
+    type_text(&mut app, "a");
+    app.update(&[]).unwrap();
+    assert_eq!(screen(&app)[0], "All > Open with Preview");
+    assert_eq!(screen(&app)[2], "│Open with Preview: a        │");
+    assert_eq!(cursor(&mut app), (21, 2));
+    assert!(matches!(
+        press(&mut app, KeyCode::Up, KeyModifiers::ALT),
+        Input::Back
+    ));
+    // back at the first level
+    app.set_breadcrumb(None).set_prompt("Query>");
+    app.update(&[]).unwrap();
+    assert_eq!(screen(&app)[1], "│Query>a                     │");
+}
+
+// Modifiers of the cells from `x` in row `y`, one per character of `len`
+fn modifiers(app: &App<Screen>, x: u16, y: u16, len: u16) -> Vec<Modifier> {
+    let buffer = app.backend().0.buffer();
+    (x..x + len).map(|x| buffer.get(x, y).modifier).collect()
+}
+
+#[test]
+fn exact_and_fuzzy_matches_highlighted_differently() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "ar");
+    let list = bins(&["ar", "tar"]);
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[5], "│   Bin  | /fixture/bin/tar  │");
+    // `ar` of `tar`, the `t` is not matched
+    assert_eq!(
+        modifiers(&app, 24, 5, 3),
+        [
+            Modifier::empty(),
+            Modifier::UNDERLINED,
+            Modifier::UNDERLINED
+        ]
+    );
+    let mut app = app_with(30, 7, Config::default());
+    type_text(&mut app, "btr");
+    app.update(&list).unwrap();
+    // `b` of `bin`, then `t` and `r` of `tar`
+    assert_eq!(modifiers(&app, 20, 5, 1), [Modifier::BOLD]);
+    assert_eq!(
+        modifiers(&app, 24, 5, 3),
+        [Modifier::BOLD, Modifier::empty(), Modifier::BOLD]
+    );
+    // off
+    let config = Config {
+        highlight_matches: false,
+        ..Config::default()
+    };
+    let mut app = app_with(30, 7, config);
+    type_text(&mut app, "ar");
+    app.update(&list).unwrap();
+    assert_eq!(modifiers(&app, 24, 5, 3), [Modifier::empty(); 3]);
+}
+
+#[test]
+fn yank_and_paste_registers() {
+    let mut app = app(30, 7);
+    type_text(&mut app, "a");
+    let list = bins(&["ar", "as"]);
+    app.update(&list).unwrap();
+    press(&mut app, KeyCode::Char('y'), KeyModifiers::ALT);
+    type_text(&mut app, "q");
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[0], "┌Yanked to \"q────────────────┐");
+    key(&mut app, KeyCode::Backspace);
+    press(&mut app, KeyCode::Char('p'), KeyModifiers::ALT);
+    type_text(&mut app, "q");
+    assert_eq!(app.get_query(), "/fixture/bin/ar");
+    // other keys cancel, and empty registers paste nothing
+    press(&mut app, KeyCode::Char('p'), KeyModifiers::ALT);
+    key(&mut app, KeyCode::Esc);
+    press(&mut app, KeyCode::Char('p'), KeyModifiers::ALT);
+    type_text(&mut app, "w");
+    assert_eq!(app.get_query(), "/fixture/bin/ar");
+}
+
+#[test]
+fn provider_status_on_the_bottom_border() {
+    let config = Config {
+        show_provider_status: true,
+        define_keyword: String::new(),
+        menu_keyword: String::new(),
+        ..Config::default()
+    };
+    let mut app = app_with(30, 6, config);
+    type_text(&mut app, "a");
+    app.update(&bins(&["ar"])).unwrap();
+    assert_eq!(screen(&app)[5], "└───────────── ● index ● url─┘");
+}
+
+#[test]
+fn histogram_of_sources() {
+    let mut app = app(50, 7);
+    type_text(&mut app, "a");
+    let mut list = bins(&["ar", "as", "awk"]);
+    list.push(LauncherResult::App("/Applications/Safari.app".to_string()));
+    app.update(&list).unwrap();
+    press(&mut app, KeyCode::Char('g'), KeyModifiers::CONTROL);
+    app.update(&list).unwrap();
+    assert_eq!(
+        &screen(&app)[3..],
+        [
+            "┌────────────────────────┐┌Sources───────────────┐",
+            "│>> Bin  | /fixture/bin/a││Bin  ███████████████ 3│",
+            "│+3 more                 ││App  █████ 1          │",
+            "└────────────────────────┘└──────────────────────┘",
+        ]
+    );
+}
+
+#[test]
+fn group_headings_with_counts() {
+    let mut app = app(30, 10);
+    type_text(&mut app, "a");
+    let mut results = bins(&["ar", "as", "awk"]);
+    results.insert(1, LauncherResult::File("/fixture/a.txt".to_string()));
+    let (list, counts) = group_results(&results, 2, None);
+    app.set_groups(Some(counts)).update(&list).unwrap();
+    let screen = screen(&app);
+    assert_eq!(screen[4], "│   Binaries (2 of 3)        │");
+    assert_eq!(screen[5], "│>> Bin  | /fixture/bin/ar   │");
+    assert_eq!(screen[6], "│   Bin  | /fixture/bin/as   │");
+    assert_eq!(screen[7], "│   Files (1)                │");
+    assert_eq!(screen[8], "│   File | /fixture/a.txt    │");
+    // the heading is not a result
+    key(&mut app, KeyCode::Down);
+    assert!(matches!(
+        press(&mut app, KeyCode::Char('e'), KeyModifiers::CONTROL),
+        Input::ExpandGroup(1)
+    ));
+}
+
+#[test]
+fn tabs_above_the_results() {
+    let mut app = app(30, 7);
+    let names = ["all", "apps", "files"].map(String::from).to_vec();
+    app.set_tabs(Some((names, 0)));
+    type_text(&mut app, "a");
+    app.update(&bins(&["ar"])).unwrap();
+    assert_eq!(screen(&app)[3], " all │ apps │ files");
+    assert_eq!(screen(&app)[5], "│>> Bin  | /fixture/bin/ar   │");
+    assert!(matches!(
+        press(&mut app, KeyCode::Tab, KeyModifiers::CONTROL),
+        Input::NextMode
+    ));
+    app.select_tab(1).update(&[]).unwrap();
+    let highlighted = &app.backend().0.buffer().get(7, 3).style().bg;
+    assert_eq!(*highlighted, Some(tui::style::Color::White));
+}
+
+#[test]
+fn selected_row_wraps_long_text() {
+    let config = Config {
+        expand_selected_subtitle: true,
+        ..Config::default()
+    };
+    let mut app = app_with(30, 9, config);
+    type_text(&mut app, "a");
+    let list = bins(&["a-rather-long-name", "ar"]);
+    app.update(&list).unwrap();
+    let lines = screen(&app);
+    assert_eq!(lines[4], "│>> Bin  | /fixture/bin/a-rat│");
+    assert_eq!(lines[5], "│   her-long-name            │");
+    assert_eq!(lines[6], "│   Bin  | /fixture/bin/ar   │");
+    // the others stay on one line
+    key(&mut app, KeyCode::Down);
+    app.update(&list).unwrap();
+    assert_eq!(screen(&app)[4], "│   Bin  | /fixture/bin/a-rat│");
+    assert_eq!(screen(&app)[5], "│>> Bin  | /fixture/bin/ar   │");
+}
+
+#[test]
+fn more_indicator_in_tiny_terminals() {
+    let list = bins(&["a1", "a2", "a3", "a4", "a5", "a6"]);
+    for (width, height) in [(1, 1), (2, 3), (3, 4), (10, 4), (2, 8)] {
+        let config = Config {
+            more_indicator: true,
+            ..Config::default()
+        };
+        let mut app = app_with(width, height, config);
+        type_text(&mut app, "a");
+        app.update(&list).unwrap();
+    }
+}
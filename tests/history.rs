@@ -0,0 +1,53 @@
+use launcher::backend::{is_private, Config};
+use launcher::calc;
+
+#[test]
+fn excluded_keywords() {
+    let config = Config {
+        history_exclude: vec!["password".to_string(), "vault".to_string()],
+        ..Config::default()
+    };
+    assert!(is_private("Cmd  | :exec echo my PASSWORD", &config));
+    assert!(is_private("Url  | vault.example.com", &config));
+    assert!(!is_private("App  | /Applications/Safari.app", &config));
+}
+
+#[test]
+fn secret_looking_words() {
+    let config = Config::default();
+    assert!(is_private("Cmd  | :exec login hunter2-Secret!", &config));
+    assert!(is_private(
+        "Cmd  | :exec curl -H ghp_x7Kd92mQpL0aZ",
+        &config
+    ));
+    // paths and ordinary words are long too
+    assert!(!is_private(
+        "File | /Users/me/Projects2023/Report-Final.pdf",
+        &config
+    ));
+    assert!(!is_private("Cmd  | :search internationalization", &config));
+    let config = Config {
+        history_exclude_secrets: false,
+        ..Config::default()
+    };
+    assert!(!is_private("Cmd  | :exec login hunter2-Secret!", &config));
+}
+
+#[test]
+fn consecutive_calculations_collapse() {
+    calc::remember("1 + 1", 2.0, None, true, true);
+    calc::remember("1 + 1", 2.0, None, true, true);
+    calc::remember("2 * 3", 6.0, None, true, true);
+    calc::remember("1 + 1", 2.0, None, true, true);
+    calc::remember("1 + 1", 2.0, None, false, true);
+    let history = calc::history(None);
+    assert_eq!(
+        history,
+        [
+            ("1 + 1".to_string(), 2.0),
+            ("1 + 1".to_string(), 2.0),
+            ("2 * 3".to_string(), 6.0),
+            ("1 + 1".to_string(), 2.0),
+        ]
+    );
+}
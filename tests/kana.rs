@@ -0,0 +1,30 @@
+use launcher::kana::to_romaji;
+
+#[test]
+fn katakana() {
+    assert_eq!(to_romaji("スラック").as_deref(), Some("surakku"));
+    assert_eq!(to_romaji("メモ").as_deref(), Some("memo"));
+    assert_eq!(to_romaji("カレンダー").as_deref(), Some("karendaa"));
+}
+
+#[test]
+fn hiragana() {
+    assert_eq!(to_romaji("さふぁり").as_deref(), Some("safari"));
+    assert_eq!(to_romaji("しゃしん").as_deref(), Some("shashin"));
+    assert_eq!(to_romaji("きょう").as_deref(), Some("kyou"));
+}
+
+#[test]
+fn small_kana() {
+    assert_eq!(to_romaji("マッチ").as_deref(), Some("matchi"));
+    assert_eq!(to_romaji("ジャーナル").as_deref(), Some("jaanaru"));
+    assert_eq!(to_romaji("ウィンドウ").as_deref(), Some("windou"));
+    assert_eq!(to_romaji("ティー").as_deref(), Some("tii"));
+}
+
+#[test]
+fn other_characters_are_kept() {
+    assert_eq!(to_romaji("スラック.app").as_deref(), Some("surakku.app"));
+    assert_eq!(to_romaji("写真ビューア").as_deref(), Some("写真byuua"));
+    assert_eq!(to_romaji("Safari"), None);
+}
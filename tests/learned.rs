@@ -0,0 +1,77 @@
+use common::scratch;
+use launcher::backend::LauncherResult;
+use launcher::{learned, state, state::StateStore};
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+mod common;
+
+fn app(name: &str) -> LauncherResult {
+    LauncherResult::App(format!("/Applications/{}.app", name))
+}
+
+fn weights(half_life_days: f64) -> Vec<(String, String, f64)> {
+    learned::selections(half_life_days)
+        .into_iter()
+        .map(|(s, weight)| (s.query, s.result, (weight * 100.0).round() / 100.0))
+        .collect()
+}
+
+// One test, what was learned is kept in memory once loaded
+#[test]
+fn picks_learned_decay_and_reset() {
+    let dir = scratch("learned");
+    // Safari picked for `safari` a month ago
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let month_ago = now - 30 * 86400;
+    fs::write(
+        dir.join("learned.json"),
+        format!(
+            r#"{{"selections": [{{"query": "safari", "result": "{}", "weight": 1.0, "time": {}}}]}}"#,
+            app("Safari").get_string(),
+            month_ago
+        ),
+    )
+    .unwrap();
+    state::configure(StateStore::new(&dir.to_string_lossy(), "json"));
+
+    // half as much after a half-life, or never forgotten
+    let safari = ("safari".to_string(), app("Safari").get_string());
+    assert_eq!(weights(30.0), [(safari.0.clone(), safari.1.clone(), 0.5)]);
+    assert_eq!(weights(0.0), [(safari.0.clone(), safari.1.clone(), 1.0)]);
+
+    // by how much of the learned query is typed
+    let mut results = vec![app("Slack"), app("Safari")];
+    assert!(!learned::boost("saf", &mut results, 30.0));
+    assert_eq!(results, [app("Slack"), app("Safari")]);
+    assert!(learned::boost("saf", &mut results, 0.0));
+    assert_eq!(results, [app("Safari"), app("Slack")]);
+    // already first
+    assert!(!learned::boost("SAFARI", &mut results, 30.0));
+    // not for what isn't a prefix
+    let mut results = vec![app("Slack"), app("Safari")];
+    assert!(!learned::boost("fari", &mut results, 0.0));
+
+    // picked again, on top of what is left of the old pick
+    learned::learn("Safari ", &app("Safari"), 30.0).unwrap();
+    learned::learn("slack", &app("Slack"), 30.0).unwrap();
+    learned::learn("  ", &app("Slack"), 30.0).unwrap();
+    assert_eq!(
+        weights(30.0),
+        [
+            (safari.0.clone(), safari.1.clone(), 1.5),
+            ("slack".to_string(), app("Slack").get_string(), 1.0),
+        ]
+    );
+    let saved = fs::read_to_string(dir.join("learned.json")).unwrap();
+    assert!(saved.contains("\"slack\""));
+
+    assert_eq!(learned::reset().unwrap(), 2);
+    assert!(weights(30.0).is_empty());
+    assert!(!dir.join("learned.json").exists());
+}
@@ -0,0 +1,53 @@
+use launcher::markdown::render;
+use tui::style::{Color, Modifier};
+
+// The rendered lines as text
+fn text(source: &str, width: usize) -> Vec<String> {
+    render(source, width)
+        .iter()
+        .map(|l| l.0.iter().map(|s| s.content.as_ref()).collect())
+        .collect()
+}
+
+#[test]
+fn headings_styled_and_set_apart() {
+    let lines = render("# Title\ntext", 20);
+    assert_eq!(text("# Title\ntext", 20), ["Title", "", "text"]);
+    let style = lines[0].0[0].style;
+    assert_eq!(style.fg, Some(Color::Cyan));
+    assert!(style
+        .add_modifier
+        .contains(Modifier::BOLD | Modifier::UNDERLINED));
+    assert_eq!(render("### Sub", 20)[0].0[0].style.fg, Some(Color::Blue));
+}
+
+#[test]
+fn paragraphs_wrap_at_the_width() {
+    assert_eq!(
+        text("one two three four five", 10),
+        ["one two ", "three four ", "five"]
+    );
+}
+
+#[test]
+fn code_blocks_keep_their_lines() {
+    let source = "```rust\nfn main() {\n\tlet long_name_past_the_width = 1;\n}\n```";
+    assert_eq!(
+        text(source, 12),
+        [
+            "rust",
+            "fn main() {",
+            "    let long_name_past_the_width = 1;",
+            "}",
+        ]
+    );
+}
+
+#[test]
+fn lists_with_markers_and_indented_wraps() {
+    assert_eq!(
+        text("- first item here\n- second", 12),
+        ["• first item ", "  here", "• second"]
+    );
+    assert_eq!(text("3. a\n4. b", 12), ["3. a", "4. b"]);
+}
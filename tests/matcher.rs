@@ -0,0 +1,117 @@
+use fuzzy_matcher::FuzzyMatcher;
+use launcher::backend::{
+    filter_keys, filter_results, match_indices, LauncherResult, MatchKind, Matcher,
+};
+
+// Names of the matches of `query` among `names`, best first
+fn ranked(names: &[&str], query: &str, matcher: &Matcher) -> Vec<String> {
+    let results = names
+        .iter()
+        .map(|n| LauncherResult::Bin(n.to_string()))
+        .collect::<Vec<LauncherResult>>();
+    filter_results(&results, &filter_keys(&results), query, matcher)
+        .iter()
+        .map(|r| r.get_content())
+        .collect()
+}
+
+#[test]
+fn defaults_prefer_separators_then_camel_case() {
+    let names = ["fabric", "fooBar", "foo_bar"];
+    assert_eq!(
+        ranked(&names, "fb", &Matcher::default()),
+        ["foo_bar", "fooBar", "fabric"]
+    );
+}
+
+#[test]
+fn a_larger_camel_case_bonus_puts_camel_case_first() {
+    let matcher = Matcher {
+        camel_case: 12,
+        ..Matcher::default()
+    };
+    assert_eq!(
+        ranked(&["fabric", "fooBar", "foo_bar"], "fb", &matcher),
+        ["fooBar", "foo_bar", "fabric"]
+    );
+}
+
+#[test]
+fn without_a_word_start_bonus_position_no_longer_matters() {
+    let matcher = Matcher {
+        word_start: 0,
+        ..Matcher::default()
+    };
+    // the same gap, only `bar` starts a word
+    let names = ["foobar", "foo bar"];
+    assert_eq!(
+        ranked(&names, "bar", &Matcher::default()),
+        ["foo bar", "foobar"]
+    );
+    assert_eq!(ranked(&names, "bar", &matcher), ["foobar", "foo bar"]);
+}
+
+#[test]
+fn the_gap_extension_penalizes_longer_gaps() {
+    let names = ["fooooooooob", "fooob"];
+    assert_eq!(
+        ranked(&names, "fb", &Matcher::default()),
+        ["fooob", "fooooooooob"]
+    );
+    let matcher = Matcher {
+        gap_extension: 0,
+        ..Matcher::default()
+    };
+    let skim = matcher.skim();
+    assert_eq!(
+        skim.fuzzy_match(names[0], "fb"),
+        skim.fuzzy_match(names[1], "fb")
+    );
+}
+
+#[test]
+fn without_a_consecutive_bonus_word_starts_win_despite_gaps() {
+    let names = ["f_a_b", "fabulous"];
+    let matcher = Matcher {
+        consecutive: 0,
+        ..Matcher::default()
+    };
+    assert_eq!(
+        ranked(&names, "fab", &Matcher::default()),
+        ["fabulous", "f_a_b"]
+    );
+    assert_eq!(ranked(&names, "fab", &matcher), ["f_a_b", "fabulous"]);
+}
+
+#[test]
+fn the_first_character_multiplier_scales_its_bonus() {
+    let skim = Matcher::default().skim();
+    let doubled = Matcher {
+        first_char_multiplier: 4,
+        ..Matcher::default()
+    }
+    .skim();
+    // `f` starts the name, its bonus of 8 counts twice and then four times
+    let score = skim.fuzzy_match("fooBar", "fb").unwrap();
+    assert_eq!(doubled.fuzzy_match("fooBar", "fb").unwrap() - score, 16);
+}
+
+#[test]
+fn contiguous_matches_are_exact() {
+    let matcher = Matcher::default();
+    assert_eq!(
+        match_indices("Activity Monitor", "MON", &matcher),
+        Some((MatchKind::Exact, vec![9, 10, 11]))
+    );
+    assert_eq!(
+        match_indices("Activity Monitor", "amo", &matcher),
+        Some((MatchKind::Fuzzy, vec![0, 9, 10]))
+    );
+    // indices are of characters, not bytes
+    assert_eq!(
+        match_indices("Café Noir", "noir", &matcher),
+        Some((MatchKind::Exact, vec![5, 6, 7, 8]))
+    );
+    assert_eq!(match_indices("Safari", "xyz", &matcher), None);
+    assert_eq!(match_indices("Safari", " ", &matcher), None);
+}
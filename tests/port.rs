@@ -0,0 +1,19 @@
+use launcher::backend::parse_lsof;
+
+#[test]
+fn processes_from_lsof_fields() {
+    let output = "p4242\ncnode\nf21\nn*:3000\nf22\nn127.0.0.1:3000->127.0.0.1:51234\np77\ncruby\nf9\nn[::1]:3000\n";
+    assert_eq!(
+        parse_lsof(output),
+        vec![
+            (4242, "node".to_string(), "*:3000".to_string()),
+            (77, "ruby".to_string(), "[::1]:3000".to_string()),
+        ]
+    );
+    assert!(parse_lsof("").is_empty());
+    // a field that isn't ASCII is skipped, not split inside a character
+    assert_eq!(
+        parse_lsof("p1\ncé\n\néx\n"),
+        vec![(1, "é".to_string(), String::new())]
+    );
+}
@@ -0,0 +1,34 @@
+use launcher::backend::LauncherResult;
+use launcher::preview::highlight;
+use std::collections::HashSet;
+
+// distinct colors of the spans of a line
+fn colors(extension: &str, code: &str) -> usize {
+    highlight(Some(extension), code, false)[0]
+        .0
+        .iter()
+        .map(|s| format!("{:?}", s.style.fg))
+        .collect::<HashSet<String>>()
+        .len()
+}
+
+#[test]
+fn snippets_are_highlighted_in_their_language() {
+    let shell = LauncherResult::Shell(
+        "up".to_string(),
+        "echo \"$HOME\" | wc -c".to_string(),
+        vec![],
+    );
+    assert_eq!(shell.snippet(), Some(("echo \"$HOME\" | wc -c", "sh")));
+    assert!(colors("sh", "echo \"$HOME\" | wc -c") > 1);
+    let script = LauncherResult::Script(
+        "mute".to_string(),
+        "set volume output muted true".to_string(),
+    );
+    assert_eq!(script.snippet().map(|s| s.1), Some("applescript"));
+    assert!(colors("applescript", "set volume output muted true") > 1);
+    assert_eq!(
+        LauncherResult::App("/Applications/Safari.app".to_string()).snippet(),
+        None
+    );
+}
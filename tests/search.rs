@@ -0,0 +1,222 @@
+use launcher::backend::{
+    app_named, filter_keys, filter_results, group_results, merge_scored, normalize_query,
+    recent_selection, refreshed_selection, Cache, Config, Generations, LauncherResult, ResultKind,
+};
+use std::{
+    collections::HashSet,
+    thread,
+    time::{Duration, Instant},
+};
+
+fn found(key: &str) -> Cache {
+    let mut delta = Cache::new();
+    delta.add_results(key, vec![LauncherResult::File(format!("/fixture/{}", key))]);
+    delta
+}
+
+#[test]
+fn stale_results_arriving_late_are_dropped() {
+    let generations = Generations::default();
+    let slow = generations.next();
+    let fast = generations.next();
+    assert!(slow.is_stale());
+    assert!(!fast.is_stale());
+
+    // the search for the newer query finishes first
+    let mut cache = Cache::new();
+    assert!(cache.merge(found("saf"), &fast));
+    assert!(!cache.merge(found("s"), &slow));
+    assert!(cache.get_results("saf").is_some());
+    assert!(cache.get_results("s").is_none());
+}
+
+#[test]
+fn a_new_round_makes_the_current_one_stale() {
+    let generations = Generations::default();
+    let round = generations.next();
+    let mut cache = Cache::new();
+    assert!(cache.merge(found("s"), &round));
+    generations.next();
+    assert!(!cache.merge(found("sa"), &round));
+    assert!(cache.get_results("s").is_some());
+}
+
+fn files(names: &[&str]) -> Vec<LauncherResult> {
+    names
+        .iter()
+        .map(|n| LauncherResult::File(format!("/fixture/{}", n)))
+        .collect()
+}
+
+#[test]
+fn selection_after_a_reordering_refresh() {
+    // `b` was selected, the refresh moved it to the end
+    let refreshed = files(&["a", "c", "d", "b"]);
+    let selected = Some("/fixture/b");
+    assert_eq!(
+        refreshed_selection("id", selected, Some(1), &refreshed),
+        Some(3)
+    );
+    assert_eq!(
+        refreshed_selection("index", selected, Some(1), &refreshed),
+        Some(1)
+    );
+    assert_eq!(
+        refreshed_selection("top", selected, Some(1), &refreshed),
+        Some(0)
+    );
+    // a result that is gone leaves the selection in place, within the list
+    let shorter = files(&["a"]);
+    assert_eq!(
+        refreshed_selection("id", selected, Some(1), &shorter),
+        Some(0)
+    );
+    assert_eq!(refreshed_selection("id", selected, Some(1), &[]), None);
+}
+
+#[test]
+fn groups_capped_until_expanded() {
+    let mut results = files(&["a", "b", "c"]);
+    results.insert(1, LauncherResult::Url("http://a.io".to_string()));
+    let (grouped, counts) = group_results(&results, 2, None);
+    assert_eq!(
+        grouped,
+        [&results[0], &results[2], &results[1]].map(Clone::clone)
+    );
+    assert_eq!(
+        counts,
+        vec![(ResultKind::File, 2, 3), (ResultKind::Url, 1, 1)]
+    );
+    let (grouped, counts) = group_results(&results, 2, Some(ResultKind::File));
+    assert_eq!(grouped.len(), 4);
+    assert_eq!(counts[0], (ResultKind::File, 3, 3));
+}
+
+#[test]
+fn recent_results_select_the_last_used() {
+    assert_eq!(recent_selection("first", 3), Some(0));
+    assert_eq!(recent_selection("second", 3), Some(1));
+    // only one to switch to
+    assert_eq!(recent_selection("second", 1), Some(0));
+    assert_eq!(recent_selection("keep", 3), None);
+    assert_eq!(recent_selection("first", 0), None);
+}
+
+#[test]
+fn expired_results_are_pruned_while_polling() {
+    let code = LauncherResult::Url("otp://123456".to_string());
+    let kept = LauncherResult::File("/fixture/notes".to_string());
+    let mut cache = Cache::new();
+    cache.add_results("12", vec![code.clone(), kept.clone()]);
+    cache.add_results("123", vec![code.clone()]);
+    cache.top_hits.insert("123".to_string());
+    cache.expire_at(&code, Instant::now() + Duration::from_millis(60));
+
+    // like the UI loop, which prunes on every tick
+    let start = Instant::now();
+    let mut ticks = 0;
+    while cache.prune(Instant::now()).is_empty() {
+        assert!(start.elapsed() < Duration::from_secs(2), "never expired");
+        thread::sleep(Duration::from_millis(10));
+        ticks += 1;
+    }
+    assert!(ticks >= 3);
+    assert_eq!(*cache.get_results("12").unwrap(), vec![kept.clone()]);
+    assert!(cache.get_results("123").unwrap().is_empty());
+    assert!(!cache.is_top_hit("123"));
+    // gone for good
+    assert!(cache.prune(Instant::now()).is_empty());
+    // also when found again
+    cache.add_results("1234", vec![code.clone(), kept.clone()]);
+    assert_eq!(
+        cache.prune(Instant::now()),
+        HashSet::from([code.get_string()])
+    );
+    assert_eq!(*cache.get_results("1234").unwrap(), vec![kept]);
+}
+
+fn scored(scores: &[(i64, &str)]) -> Vec<(i64, LauncherResult)> {
+    scores
+        .iter()
+        .map(|(score, name)| (*score, LauncherResult::File(format!("/fixture/{}", name))))
+        .collect()
+}
+
+fn names(results: &[LauncherResult]) -> Vec<String> {
+    results
+        .iter()
+        .map(|r| r.get_content().trim_start_matches("/fixture/").to_string())
+        .collect()
+}
+
+#[test]
+fn providers_interleave_after_normalizing_scores() {
+    // the file index scores in the hundreds, the menu in units
+    let providers = || {
+        vec![
+            scored(&[(1000, "f1"), (900, "f2"), (100, "f3")]),
+            scored(&[(10, "m1"), (2, "m2")]),
+        ]
+    };
+    assert_eq!(
+        names(&merge_scored(providers(), "none")),
+        ["f1", "f2", "f3", "m1", "m2"]
+    );
+    // ties keep the order of the providers
+    assert_eq!(
+        names(&merge_scored(providers(), "min-max")),
+        ["f1", "m1", "f2", "f3", "m2"]
+    );
+    // m2 is one deviation below the menu's mean, f3 more than that below the files'
+    assert_eq!(
+        names(&merge_scored(providers(), "z-score")),
+        ["m1", "f1", "f2", "m2", "f3"]
+    );
+    // a single result is as good as the best of the others
+    let single = vec![scored(&[(50, "f1"), (10, "f2")]), scored(&[(3, "m1")])];
+    assert_eq!(names(&merge_scored(single, "min-max")), ["f1", "m1", "f2"]);
+}
+
+#[test]
+fn composed_and_decomposed_queries_find_the_same() {
+    let composed = "caf\u{e9}";
+    let decomposed = "cafe\u{301}";
+    assert_eq!(normalize_query(decomposed), composed);
+    assert_eq!(
+        normalize_query("  caf\u{e9}   au  \"lait  chaud\" "),
+        "café au \"lait  chaud\""
+    );
+
+    // one name of each form
+    let results = vec![
+        LauncherResult::File("/fixture/Cafe\u{301} Menu.pdf".to_string()),
+        LauncherResult::File("/fixture/Caf\u{e9} Bills.pdf".to_string()),
+        LauncherResult::File("/fixture/Tea.pdf".to_string()),
+    ];
+    let keys = filter_keys(&results);
+    let matcher = Config::default().matcher;
+    let found = |query: &str| filter_results(&results, &keys, &normalize_query(query), &matcher);
+    assert_eq!(found(composed).len(), 2);
+    assert_eq!(found(composed), found(decomposed));
+    // what is shown keeps its form
+    assert_eq!(found(decomposed)[0].get_string(), results[0].get_string());
+}
+
+#[test]
+fn commands_are_searched_as_typed() {
+    assert_eq!(
+        normalize_query(" :echo  a   \"b  c\"  d "),
+        ":echo  a   \"b  c\"  d"
+    );
+    assert_eq!(normalize_query(":echo cafe\u{301}"), ":echo cafe\u{301}");
+}
+
+#[test]
+fn apps_taking_arguments_by_name_or_word() {
+    assert_eq!(app_named("Safari.app", "safari"), Some(true));
+    assert_eq!(app_named("Visual Studio Code.app", "Code"), Some(false));
+    assert_eq!(app_named("Google Chrome.app", "google chrome"), Some(true));
+    // not the start of a word
+    assert_eq!(app_named("Safari.app", "saf"), None);
+    assert_eq!(app_named("TextEdit.app", "text"), None);
+}
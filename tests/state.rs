@@ -0,0 +1,45 @@
+use common::scratch;
+use launcher::state::StateStore;
+use serde_derive::{Deserialize, Serialize};
+
+mod common;
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+struct Recent {
+    results: Vec<String>,
+}
+
+fn recent(results: &[&str]) -> Recent {
+    Recent {
+        results: results.iter().map(|r| r.to_string()).collect(),
+    }
+}
+
+#[test]
+fn stores_survive_a_change_of_format() {
+    let dir = scratch("format");
+    let toml = StateStore::new(&dir.to_string_lossy(), "toml");
+    assert_eq!(toml.load::<Recent>("recent"), None);
+    toml.save("recent", &recent(&["a", "b"])).unwrap();
+    assert!(dir.join("recent.toml").exists());
+
+    // read from the old file, then replaced by one in the new format
+    let json = StateStore::new(&dir.to_string_lossy(), "json");
+    assert_eq!(json.load("recent"), Some(recent(&["a", "b"])));
+    json.save("recent", &recent(&["c"])).unwrap();
+    assert!(dir.join("recent.json").exists());
+    assert!(!dir.join("recent.toml").exists());
+    assert!(!dir.join("recent.tmp").exists());
+    assert_eq!(toml.load("recent"), Some(recent(&["c"])));
+}
+
+#[test]
+fn reset_forgets_every_store() {
+    let dir = scratch("reset");
+    let store = StateStore::new(&dir.to_string_lossy(), "json");
+    store.save("recent", &recent(&["a"])).unwrap();
+    store.save("learned", &recent(&["b"])).unwrap();
+    assert_eq!(store.reset().unwrap(), ["recent", "learned"]);
+    assert_eq!(store.load::<Recent>("recent"), None);
+    assert!(store.reset().unwrap().is_empty());
+}
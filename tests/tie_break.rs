@@ -0,0 +1,57 @@
+use common::{scratch, Scratch};
+use launcher::backend::{Cache, Config, FileEntryType, LauncherResult, Query, Scope};
+use launcher::{learned, state, state::StateStore};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+mod common;
+
+// Two apps scoring the same, `Notes B` modified a day after `Notes A`
+fn apps(name: &str) -> (Scratch, PathBuf, PathBuf) {
+    let dir = scratch(name);
+    let (a, b) = (dir.join("Notes A.app"), dir.join("Notes B.app"));
+    let day = Duration::from_secs(86400);
+    let now = SystemTime::now();
+    File::create(&a).unwrap().set_modified(now - day).unwrap();
+    File::create(&b).unwrap().set_modified(now).unwrap();
+    (dir, a, b)
+}
+
+fn found(dir: &Path, tie_break: &str) -> Vec<LauncherResult> {
+    let config = Config {
+        app_locations: vec![dir.to_string_lossy().to_string()],
+        tie_break: HashMap::from([("app".to_string(), tie_break.to_string())]),
+        ..Config::default()
+    };
+    let query = Query::with_scope("notes", Scope::Only(FileEntryType::App));
+    let delta = query.parse(&config, Cache::init(&config)).unwrap();
+    let results = delta.get_results(&Scope::Only(FileEntryType::App).key("notes"));
+    results.unwrap().to_vec()
+}
+
+fn app(path: &Path) -> LauncherResult {
+    LauncherResult::App(path.to_string_lossy().to_string())
+}
+
+#[test]
+fn equal_scores_newest_first() {
+    let (dir, a, b) = apps("mtime");
+    assert_eq!(found(&dir, "mtime")[..2], [app(&b), app(&a)]);
+    assert_eq!(found(&dir, "name")[..2], [app(&a), app(&b)]);
+}
+
+#[test]
+fn equal_scores_most_picked_first() {
+    let (dir, a, b) = apps("frecency");
+    state::configure(StateStore::new(
+        &dir.join("state").to_string_lossy(),
+        "json",
+    ));
+    // picked for other queries, it still counts
+    learned::learn("old notes", &app(&a), 30.0).unwrap();
+    assert_eq!(found(&dir, "frecency")[..2], [app(&a), app(&b)]);
+}
@@ -0,0 +1,47 @@
+use launcher::backend::{match_toggles, parse_toggle_state, LauncherResult, Toggle};
+
+fn toggle(state: &str) -> Toggle {
+    Toggle {
+        state: state.to_string(),
+        on: "true".to_string(),
+        off: "true".to_string(),
+    }
+}
+
+fn found(query: &str) -> Vec<String> {
+    let toggles = [
+        ("Test Lights".to_string(), toggle("lights")),
+        ("Test Fan".to_string(), toggle("fan")),
+    ];
+    // the fan's state can't be read
+    let state = |t: &Toggle| (t.state == "lights").then_some(true);
+    match_toggles(query, &toggles, state)
+        .iter()
+        .filter(|r| matches!(r, LauncherResult::Script(..)))
+        .map(|r| r.get_string())
+        .collect()
+}
+
+#[test]
+fn toggles_show_their_state() {
+    assert_eq!(found("test lig"), ["Scpt | Test Lights: on, turn off"]);
+    assert_eq!(
+        found("toggle test lights on"),
+        ["Scpt | Test Lights: on, turn on"]
+    );
+    // a state that can't be read offers both
+    assert_eq!(
+        found("test fan"),
+        ["Scpt | Turn Test Fan on", "Scpt | Turn Test Fan off"]
+    );
+    assert!(found("te").is_empty());
+}
+
+#[test]
+fn toggle_states_from_their_output() {
+    assert_eq!(parse_toggle_state(true, "1\n"), Some(true));
+    assert_eq!(parse_toggle_state(true, " On "), Some(true));
+    assert_eq!(parse_toggle_state(true, "false"), Some(false));
+    assert_eq!(parse_toggle_state(true, "maybe"), None);
+    assert_eq!(parse_toggle_state(false, "1"), None);
+}
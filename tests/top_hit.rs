@@ -0,0 +1,50 @@
+use common::{scratch, Scratch};
+use launcher::backend::{Cache, Config, FileEntryType, Query, Scope};
+use std::{fs, path::Path};
+
+mod common;
+
+// Apps of these names in a directory of their own
+fn apps(name: &str, apps: &[&str]) -> Scratch {
+    let dir = scratch(name);
+    for app in apps {
+        fs::write(dir.join(app), "").unwrap();
+    }
+    dir
+}
+
+fn is_top_hit(dir: &Path, query: &str, config: Config) -> bool {
+    let config = Config {
+        app_locations: vec![dir.to_string_lossy().to_string()],
+        ..config
+    };
+    let scope = Scope::Only(FileEntryType::App);
+    let delta = Query::with_scope(query, scope.clone())
+        .parse(&config, Cache::init(&config))
+        .unwrap();
+    assert!(delta.get_results(&scope.key(query)).is_some());
+    delta.is_top_hit(&scope.key(query))
+}
+
+#[test]
+fn top_hit_needs_a_score_per_character() {
+    let dir = apps("score", &["Safari.app", "Slack.app"]);
+    assert!(is_top_hit(&dir, "safari", Config::default()));
+    let strict = Config {
+        top_hit_min_score: 1000,
+        ..Config::default()
+    };
+    assert!(!is_top_hit(&dir, "safari", strict));
+}
+
+#[test]
+fn top_hit_ahead_of_the_next_by_the_margin() {
+    let dir = apps("margin", &["Notes A.app", "Notes B.app"]);
+    // both score the same
+    assert!(!is_top_hit(&dir, "notes", Config::default()));
+    let tied = Config {
+        top_hit_margin: 0,
+        ..Config::default()
+    };
+    assert!(is_top_hit(&dir, "notes", tied));
+}